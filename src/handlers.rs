@@ -1,8 +1,66 @@
-use axum::{Json, http::StatusCode};
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::{Json, extract::State, http::StatusCode};
 use serde_json::json;
 
+use crate::models::analytics;
+use crate::models::book::{self, OpeningBook};
+use crate::models::distance::DistanceMap;
+use crate::models::duel;
+use crate::models::opponents::GameHistory;
+use crate::models::phase::Phase;
+use crate::models::risk::RiskProfile;
+use crate::models::state::GameState;
+use crate::models::transposition::TranspositionTable;
 use crate::models::*;
 
+// what we keep about one game between its requests
+#[derive(Default)]
+struct GameSession {
+    history: GameHistory,
+    // only made once the game is down to a duel
+    duel_table: Option<Arc<TranspositionTable>>,
+}
+
+impl GameSession {
+    fn get_duel_table(self: &mut Self) -> Arc<TranspositionTable> {
+        return self
+            .duel_table
+            .get_or_insert_with(|| Arc::new(duel::new_duel_table()))
+            .clone();
+    }
+}
+
+// per game state kept between requests, keyed by game id, and the settings
+// the server was started with
+#[derive(Clone)]
+pub struct AppState {
+    games: Arc<Mutex<HashMap<String, GameSession>>>,
+    // how boldly to bet on what opponents will do
+    risk: RiskProfile,
+    book: Arc<OpeningBook>,
+}
+
+impl AppState {
+    pub fn new(risk: RiskProfile, book: OpeningBook) -> AppState {
+        return AppState {
+            games: Arc::default(),
+            risk,
+            book: Arc::new(book),
+        };
+    }
+
+    // a panic while the lock was held only loses that game's session,
+    // the map itself is still usable
+    fn lock_games(self: &Self) -> MutexGuard<'_, HashMap<String, GameSession>> {
+        return self.games.lock().unwrap_or_else(|e| e.into_inner());
+    }
+}
+
 // #[axum::debug_handler]
 pub async fn get_battlesnake_details() -> Json<BattlesnakeDetails> {
     let details = BattlesnakeDetails::get();
@@ -15,37 +73,152 @@ pub async fn game_start_handler(Json(_payload): Json<GameStart>) -> StatusCode {
     // format!("{} OK", payload.turn)
 }
 
-pub async fn move_handler(Json(payload): Json<MoveRequest>) -> Json<serde_json::Value> {
+pub async fn move_handler(
+    State(state): State<AppState>,
+    Json(mut payload): Json<MoveRequest>,
+) -> Json<serde_json::Value> {
+    payload.apply_ruleset();
+
+    // scoring is pure cpu work, keep it off the runtime threads so other
+    // games' requests are still answered while it runs
+    let direction =
+        match tokio::task::spawn_blocking(move || choose_direction(&state, &payload)).await {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("[error] move task failed: {}", e);
+                Direction::Up.get_str()
+            }
+        };
+
+    Json(json!({"move": direction}))
+}
+
+fn choose_direction(state: &AppState, payload: &MoveRequest) -> String {
+    // a malformed or unusual payload must never cost us the turn, so any
+    // panic while scoring falls back to the simplest legal move
+    let result = panic::catch_unwind(AssertUnwindSafe(|| score_move(state, payload)));
+
+    return match result {
+        Ok(m) => m.get_direction_str(),
+        Err(e) => {
+            eprintln!(
+                "[error] game {} turn {}: move scoring panicked: {}",
+                payload.get_game_id(),
+                payload.get_turn(),
+                panic_message(&e)
+            );
+
+            let fallback = panic::catch_unwind(AssertUnwindSafe(|| {
+                payload
+                    .get_you_ref()
+                    .get_fallback_move(payload.get_board_ref())
+            }));
+
+            fallback.unwrap_or(Direction::Up).get_str()
+        }
+    };
+}
+
+fn score_move(state: &AppState, payload: &MoveRequest) -> ScoredMove {
     let mut sm = ScoredMoves::init();
     let b = payload.get_board_ref();
 
     let you = payload.get_you_ref();
 
+    let (predictions, duel_table) = {
+        let mut games = state.lock_games();
+        let session = games.entry(payload.get_game_id()).or_default();
+        session.history.observe(payload.get_turn(), b, you);
+
+        let duel_table = if you.is_in_duel(b) {
+            Some(session.get_duel_table())
+        } else {
+            None
+        };
+
+        (session.history.predict(b, you), duel_table)
+    };
+
+    let risk = state.risk;
+    let book = &state.book;
+
+    // what matters most changes as the game goes on
+    let phase = Phase::detect(b, payload.get_turn());
+    let weights = phase.get_weights();
+    println!("[phase]: {:?}", phase);
+
+    // shared by every heuristic that needs to know who gets where first
+    let distances = DistanceMap::new(b);
+    // and by everything that plays moves out under the real rules
+    let mut state = GameState::new(b);
+
     you.avoid_own_neck(&mut sm, b);
 
     you.avoid_walls(&mut sm, b);
 
     you.avoid_any_snake(&mut sm, b);
 
+    you.avoid_certain_death(&mut sm, b, &mut state);
+
+    // the first turns of a standard game were worked out ahead of time.
+    // sealed away from everyone, only outlasting them matters. with one
+    // opponent left the search sees further than any heuristic
+    if you.use_opening_book(
+        &mut sm,
+        b,
+        book,
+        payload.get_turn(),
+        payload.get_ruleset_name(),
+    ) || you.solve_endgame(&mut sm, b)
+        || duel_table.is_some_and(|table| {
+            you.search_duel(&mut sm, b, &state, &table, payload.get_search_budget())
+        })
+    {
+        let chosen_move = you.choose_move(&sm);
+
+        println!("{:?}", sm);
+        println!("{:?}", chosen_move);
+
+        return chosen_move;
+    }
+
     //prefer direction of closest food...
     // TODO: tune the weighting of the food preference
     // TODO: tune the weight of moving  toward tail
     // TODO: use flood fill results more effectively..
 
-    if you.is_longest_snake_on_board(b) {
-        you.move_toward_tail(&mut sm, b);
-    }
+    if you.is_starving(b) {
+        // we'll die of hunger soon, nothing else matters as much
+        you.move_toward_food_urgently(&mut sm, b);
+    } else {
+        if you.is_longest_snake_on_board(b) {
+            you.move_toward_tail(&mut sm, b);
+        }
 
-    if you.get_missing_health() > 50 || !you.is_longest_snake_on_board(b) {
-        you.move_toward_food(&mut sm, b);
+        if you.get_missing_health() > 50 || !you.is_longest_snake_on_board(b) {
+            sm.apply_weighted(weights.food, |sm| {
+                you.move_toward_food(sm, b, &distances, &predictions, &risk)
+            });
+        }
     }
 
     // if !you.is_longest_snake_on_board(b) {}
 
-    you.attack_smaller_snake_head(&mut sm, b);
-    you.avoid_larger_snake_head(&mut sm, b);
+    sm.apply_weighted(weights.aggression, |sm| {
+        you.hunt_smaller_snakes(sm, b, &distances, &predictions)
+    });
+    // close up the game theory replaces the per-move guess at who wins a
+    // head to head
+    if !you.play_head_to_heads(&mut sm, b, &mut state, &predictions, &risk) {
+        you.avoid_larger_snake_head(&mut sm, b, &predictions, &risk);
+    }
+
+    sm.apply_weighted(weights.space, |sm| you.use_flood_fill(sm, b));
+    sm.apply_weighted(weights.territory, |sm| you.use_territory(sm, b));
+    sm.apply_weighted(weights.chokepoints, |sm| you.use_chokepoints(sm, b));
+    sm.apply_weighted(weights.aggression, |sm| you.use_cut_offs(sm, b));
 
-    you.use_flood_fill(&mut sm, b);
+    you.survive_when_trapped(&mut sm, b);
 
     //println!("choosing best move...");
     let chosen_move = you.choose_move(&sm);
@@ -54,10 +227,95 @@ pub async fn move_handler(Json(payload): Json<MoveRequest>) -> Json<serde_json::
     println!("{:?}", sm);
     println!("{:?}", chosen_move);
 
-    Json(json!({"move": chosen_move.get_direction_str()}))
+    return chosen_move;
 }
 
-pub async fn game_end_handler(Json(_payload): Json<GameOver>) -> StatusCode {
+fn panic_message(e: &Box<dyn Any + Send>) -> String {
+    if let Some(s) = e.downcast_ref::<&str>() {
+        return s.to_string();
+    }
+
+    if let Some(s) = e.downcast_ref::<String>() {
+        return s.clone();
+    }
+
+    return "unknown panic".to_string();
+}
+
+pub async fn game_end_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<GameOver>,
+) -> StatusCode {
     //println!("{:?}", payload);
+    let session = state.lock_games().remove(&payload.get_game_id());
+
+    let record = payload.get_record(session.as_ref().map(|s| &s.history));
+    println!("{:?}", record);
+
+    if let Err(e) = analytics::append_record(&analytics::results_path(), &record) {
+        eprintln!("failed to save game result: {}", e);
+    }
+
     StatusCode::OK
 }
+
+// how long each book position gets to be worked out, far more than a real
+// turn allows
+const BOOK_TIMEOUT_MS: u16 = 5000;
+
+// fill in the opening book from recorded move requests, one json request
+// per line. positions already in the book are left alone
+pub fn generate_book(requests_path: &str) -> std::io::Result<usize> {
+    let path = book::book_path();
+    let mut book = match OpeningBook::load(&path) {
+        Ok(b) => b,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => OpeningBook::default(),
+        Err(e) => return Err(e),
+    };
+
+    let file = std::fs::File::open(requests_path)?;
+    // worked out from scratch, not from whatever the book already says
+    let state = AppState::new(RiskProfile::from_env(), OpeningBook::default());
+    let mut added = 0;
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut payload: MoveRequest = match serde_json::from_str(&line) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("skipping bad request line: {}", e);
+                continue;
+            }
+        };
+
+        payload.apply_ruleset();
+        payload.set_timeout(BOOK_TIMEOUT_MS);
+
+        let b = payload.get_board_ref();
+        let you = payload.get_you_ref();
+        let turn = payload.get_turn();
+
+        if turn > book::BOOK_TURNS || book.covers(b, you, turn, payload.get_ruleset_name()) {
+            continue;
+        }
+
+        let chosen = choose_direction(&state, &payload);
+        let direction = match Direction::ALL.into_iter().find(|d| d.get_str() == chosen) {
+            Some(d) => d,
+            None => continue,
+        };
+
+        if book.insert(b, you, turn, payload.get_ruleset_name(), direction) {
+            added += 1;
+        }
+    }
+
+    book.save(&path)?;
+    println!("{} positions added, {} in {}", added, book.len(), path);
+
+    return Ok(added);
+}
@@ -9,14 +9,49 @@ use axum::{
 };
 
 use handlers::*;
+use models::analytics;
+use models::book::{self, OpeningBook};
+use models::risk::RiskProfile;
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    // `battlesnake-rs report [N]` summarises the last N games instead of serving
+    if args.len() > 1 && args[1] == "report" {
+        let last_n: usize = args.get(2).and_then(|n| n.parse().ok()).unwrap_or(50);
+        let path = analytics::results_path();
+
+        match analytics::load_records(&path) {
+            Ok(records) => print!("{}", analytics::report(&records, last_n)),
+            Err(e) => eprintln!("could not read {}: {}", path, e),
+        }
+        return;
+    }
+
+    // `battlesnake-rs book <requests.jsonl>` adds the opening positions in a
+    // file of recorded move requests to the opening book
+    if args.len() > 2 && args[1] == "book" {
+        if let Err(e) = generate_book(&args[2]) {
+            eprintln!("could not build the book from {}: {}", args[2], e);
+        }
+        return;
+    }
+
+    // how boldly to bet on what opponents will do, set per deployment
+    let risk = RiskProfile::from_env();
+    println!("[risk]: {:?}", risk);
+
+    let book_path = book::book_path();
+    let book = OpeningBook::load_or_empty(&book_path);
+    println!("[book]: {} positions from {}", book.len(), book_path);
+
     let app = Router::new()
         .route("/", get(get_battlesnake_details))
         .route("/start", post(game_start_handler))
         .route("/move", post(move_handler))
-        .route("/end", post(game_end_handler));
+        .route("/end", post(game_end_handler))
+        .with_state(AppState::new(risk, book));
 
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
         .await
@@ -1,7 +1,40 @@
-use core::f32;
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
-use std::{collections::HashMap, hash::Hash};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+pub mod analytics;
+pub mod book;
+pub mod chokepoints;
+pub mod distance;
+pub mod duel;
+pub mod endgame;
+#[cfg(test)]
+mod fixtures;
+pub mod food;
+pub mod geometry;
+pub mod grid;
+pub mod health;
+pub mod hunting;
+pub mod nash;
+pub mod offense;
+pub mod opponents;
+pub mod parallel;
+pub mod pathfinding;
+pub mod phase;
+pub mod risk;
+pub mod state;
+pub mod survival;
+pub mod territory;
+pub mod transposition;
+pub mod zobrist;
+
+use distance::{DistanceMap, UNREACHABLE, vacate_times};
+use grid::Grid;
+use opponents::MovePredictions;
+use pathfinding::PathFinder;
+use risk::{Decision, RiskProfile};
+use transposition::{Bound, TranspositionTable};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Direction {
@@ -12,6 +45,13 @@ pub enum Direction {
 }
 
 impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Down,
+        Direction::Up,
+    ];
+
     pub fn get_str(self: &Self) -> String {
         match self {
             Direction::Left => "left".to_string(),
@@ -26,6 +66,8 @@ impl Direction {
 pub struct ScoredMove {
     direction: Direction,
     score: i16,
+    // ruled out as certain death, whatever later heuristics add to the score
+    dead: bool,
 }
 
 impl ScoredMove {
@@ -34,7 +76,7 @@ impl ScoredMove {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ScoredMoves {
     Left: ScoredMove,
     Right: ScoredMove,
@@ -49,18 +91,22 @@ impl ScoredMoves {
         let l: ScoredMove = ScoredMove {
             direction: Direction::Left,
             score: 0,
+            dead: false,
         };
         let r: ScoredMove = ScoredMove {
             direction: Direction::Right,
             score: 0,
+            dead: false,
         };
         let d: ScoredMove = ScoredMove {
             direction: Direction::Down,
             score: 0,
+            dead: false,
         };
         let u: ScoredMove = ScoredMove {
             direction: Direction::Up,
             score: 0,
+            dead: false,
         };
 
         return ScoredMoves {
@@ -74,11 +120,49 @@ impl ScoredMoves {
     fn iter(&self) -> impl Iterator<Item = &ScoredMove> {
         [&self.Left, &self.Right, &self.Down, &self.Up].into_iter()
     }
+
+    fn get(self: &Self, dir: Direction) -> &ScoredMove {
+        match dir {
+            Direction::Left => &self.Left,
+            Direction::Right => &self.Right,
+            Direction::Down => &self.Down,
+            Direction::Up => &self.Up,
+        }
+    }
+
+    fn get_mut(self: &mut Self, dir: Direction) -> &mut ScoredMove {
+        match dir {
+            Direction::Left => &mut self.Left,
+            Direction::Right => &mut self.Right,
+            Direction::Down => &mut self.Down,
+            Direction::Up => &mut self.Up,
+        }
+    }
+
+    fn kill(self: &mut Self, dir: Direction) {
+        let m = self.get_mut(dir);
+        m.score = ScoredMoves::DEATH;
+        m.dead = true;
+    }
+
+    fn is_dead(self: &Self, dir: Direction) -> bool {
+        return self.get(dir).dead;
+    }
+
+    // the moves nothing has ruled out as certain death
+    fn get_alive_moves(self: &Self) -> Vec<Direction> {
+        return self
+            .iter()
+            .filter(|m| !m.dead)
+            .map(|m| m.direction)
+            .collect();
+    }
 }
 
 impl Ord for ScoredMove {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.score.cmp(&other.score)
+        // a dead move never outranks a live one
+        (!self.dead, self.score).cmp(&(!other.dead, other.score))
     }
 }
 
@@ -88,80 +172,25 @@ impl PartialOrd for ScoredMove {
     }
 }
 
-#[derive(Copy, Debug, Deserialize, Serialize, Clone, Hash)]
+#[derive(Copy, Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
 pub struct Coord {
+    #[serde(deserialize_with = "deserialize_axis")]
     x: u16,
+    #[serde(deserialize_with = "deserialize_axis")]
     y: u16,
 }
 
-pub fn reconstruct_path(mut current: Coord, came_from: &HashMap<Coord, Coord>) -> Vec<Coord> {
-    let mut path = vec![current];
-    while let Some(&prev) = came_from.get(&current) {
-        current = prev;
-        path.push(current.clone());
-    }
-    path.reverse();
-    return path;
+// a snake that died on a wall has its head at -1 in the /end payload.
+// anything that doesn't fit a u16 reads as u16::MAX, which is off every board
+fn deserialize_axis<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let v = i64::deserialize(deserializer)?;
+    return Ok(u16::try_from(v).unwrap_or(u16::MAX));
 }
 
 impl Coord {
-    pub fn contains_larger_snake_head(self: &Self, me: &Battlesnake, b: &Board) -> bool {
-        for snake in &b.snakes {
-            if snake.head.x == self.x && snake.head.y == self.y {
-                if snake.length >= me.length {
-                    return true;
-                }
-            }
-        }
-
-        return false;
-    }
-
-    pub fn contains_smaller_snake_head(self: &Self, me: &Battlesnake, b: &Board) -> bool {
-        for snake in &b.snakes {
-            if snake.head.x == self.x && snake.head.y == self.y {
-                if snake.length < me.length {
-                    return true;
-                }
-            }
-        }
-
-        return false;
-    }
-
-    pub fn get_next_coord(self: &Self, dir: Direction) -> Coord {
-        match dir {
-            Direction::Left => Coord {
-                x: self.x - 1,
-                y: self.y,
-            },
-            Direction::Right => Coord {
-                x: self.x + 1,
-                y: self.y,
-            },
-            Direction::Up => Coord {
-                x: self.x,
-                y: self.y + 1,
-            },
-            Direction::Down => Coord {
-                x: self.x,
-                y: self.y - 1,
-            },
-        }
-    }
-
-    pub fn is_in_bounds(self: &Self, b: &Board) -> bool {
-        if self.x >= b.width {
-            return false;
-        }
-
-        if self.y >= b.height {
-            return false;
-        }
-
-        return true;
-    }
-
     pub fn is_in_snakeBody(self: &Self, b: &Board) -> bool {
         let snakes = &b.snakes;
 
@@ -193,150 +222,104 @@ impl Coord {
     // pub fn is_in_own_body(self: &Self, b: &Board) -> bool {
     //     let me = &b.
     // }
+}
 
-    pub fn get_direction_to(self: &Self, other: &Coord) -> Direction {
-        //left
-
-        if self.x > 0 {
-            if self.x - 1 == other.x && self.y == other.y {
-                return Direction::Left;
-            }
-        }
-
-        // right
-
-        if self.x + 1 == other.x && self.y == other.y {
-            return Direction::Right;
-        }
-
-        // down
-
-        if self.y > 0 {
-            if self.x == other.x && self.y - 1 == other.y {
-                return Direction::Down;
-            }
-        }
-
-        // up
-
-        if self.x == other.x && self.y + 1 == other.y {
-            return Direction::Up;
-        }
-
-        return Direction::Left;
-    }
-
-    pub fn get_distance_to(self: &Self, other: &Coord) -> u16 {
-        let distanceX = self.x as i16 - other.x as i16;
-        let distanceY = self.y as i16 - other.x as i16;
-
-        let total = distanceX.abs() + distanceY.abs();
+// number of cells we could get to after stepping onto `s`. a body segment
+// doesn't block us if it will have moved out of the way by the time we
+// arrive, and since we can burn moves looping around the cells we already
+// have, a pocket that opens up later still counts as escapable. the same
+// board and cell get filled several times a move, so results are cached
+pub fn flood_fill(b: &Board, s: Coord) -> i16 {
+    let key = match Grid::new(b).index(&s) {
+        Some(i) => b.zobrist_hash() ^ zobrist::start_key(i),
+        None => return 0,
+    };
 
-        return total as u16;
+    if let Some(v) = EVALUATIONS.with(|tt| tt.probe(key, 0)) {
+        return v;
     }
 
-    pub fn get_neighbours(self: Self, b: &Board) -> Vec<Coord> {
-        let mut nbs: Vec<Coord> = Vec::new();
-
-        if self.x > 0 {
-            nbs.push(Coord {
-                x: self.x - 1,
-                y: self.y,
-            });
-        }
-
-        if self.x + 1 < b.width {
-            nbs.push(Coord {
-                x: self.x + 1,
-                y: self.y,
-            });
-        }
-
-        if self.y > 0 {
-            nbs.push(Coord {
-                x: self.x,
-                y: self.y - 1,
-            });
-        }
+    let v = count_flood_fill(b, s);
+    EVALUATIONS.with(|tt| tt.store(key, 0, v, Bound::Exact, None));
 
-        if self.y + 1 < b.height {
-            nbs.push(Coord {
-                x: self.x,
-                y: self.y + 1,
-            });
-        }
-
-        return nbs;
-    }
+    return v;
 }
 
-impl PartialEq for Coord {
-    fn eq(&self, other: &Self) -> bool {
-        if self.x == other.x && self.y == other.y {
-            return true;
-        } else {
-            return false;
-        }
-    }
+fn count_flood_fill(b: &Board, s: Coord) -> i16 {
+    let reached = flood_fill_arrivals(b, s)
+        .iter()
+        .filter(|a| **a != UNREACHABLE)
+        .count();
+
+    return reached.try_into().unwrap_or(i16::MAX);
 }
 
-impl Eq for Coord {}
+// when each cell flood_fill counts is first stood on, UNREACHABLE for the
+// rest. indexed like Grid
+pub fn flood_fill_arrivals(b: &Board, s: Coord) -> Vec<u16> {
+    let grid = Grid::new(b);
+    let free_at = vacate_times(b, &grid);
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-pub struct CoordWithDistance {
-    x: u16,
-    y: u16,
-    distance: u16,
-}
+    let mut arrival: Vec<u16> = vec![UNREACHABLE; grid.cells()];
+    let mut q: VecDeque<usize> = VecDeque::new();
+    let mut blocked: Vec<usize> = Vec::new();
 
-impl Ord for CoordWithDistance {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.distance.cmp(&other.distance)
-    }
-}
+    let start = match grid.index(&s) {
+        Some(i) => i,
+        None => return arrival,
+    };
 
-impl PartialOrd for CoordWithDistance {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+    // stepping onto `s` is our first move
+    if free_at[start] > 1 {
+        return arrival;
     }
-}
 
-pub fn flood_fill(b: &Board, s: Coord) -> i16 {
-    let start = std::time::Instant::now();
+    let mut reached: u16 = 1;
+    arrival[start] = 1;
+    q.push_back(start);
 
-    let mut q: Vec<Coord> = Vec::new();
-    let mut avail: Vec<Coord> = Vec::new();
-    // let mut iters: i16 = 0;
+    let mut nbs = [0usize; 4];
 
-    q.push(s);
+    loop {
+        while let Some(cell) = q.pop_front() {
+            let t = arrival[cell] + 1;
 
-    while !q.is_empty() && start.elapsed().as_millis() < 150 {
-        // iters += 1;
-        let n = q[0];
-        q.remove(0);
+            let n = grid.neighbours(cell, &mut nbs);
+            for &next in &nbs[..n] {
+                if arrival[next] != UNREACHABLE {
+                    continue;
+                }
 
-        if n.is_in_bounds(b) && !n.is_in_snakeBody(b) {
-            if !avail.contains(&n) {
-                avail.push(n);
+                if free_at[next] > t {
+                    blocked.push(next);
+                    continue;
+                }
 
-                let good_neighbours = n.get_neighbours(b);
+                arrival[next] = t;
+                reached += 1;
+                q.push_back(next);
+            }
+        }
 
-                for n in good_neighbours {
-                    q.push(n);
-                }
+        // the longest we can stall is roughly one lap of what we've found
+        let mut opened = false;
+        for &cell in &blocked {
+            if arrival[cell] == UNREACHABLE && free_at[cell] <= reached + 1 {
+                arrival[cell] = free_at[cell];
+                reached += 1;
+                q.push_back(cell);
+                opened = true;
             }
         }
-    }
 
-    return avail.len().try_into().unwrap();
-}
+        if !opened {
+            break;
+        }
 
-#[derive(Clone, Copy)]
-pub struct Node {
-    coord: Coord,
-    g_score: f32,
-    f_score: f32,
-    came_from: Option<Coord>,
+        blocked.retain(|c| arrival[*c] == UNREACHABLE);
+    }
+
+    return arrival;
 }
 
 pub fn _path_is_clear(p: &Vec<Coord>, b: &Board) -> bool {
@@ -349,72 +332,26 @@ pub fn _path_is_clear(p: &Vec<Coord>, b: &Board) -> bool {
     return true;
 }
 
-pub fn a_star_path_find(start: Coord, end: Coord, b: &Board) -> Option<Vec<Coord>> {
-    //-> Vec<Coord>
-    let mut open_set: HashMap<Coord, Node> = HashMap::new();
-    let mut closed_set: HashMap<Coord, Node> = HashMap::new();
-    let mut came_from: HashMap<Coord, Coord> = HashMap::new();
-
-    let start_node = Node {
-        coord: start.clone(),
-        g_score: 0.0,
-        f_score: heuristic_from_n_to_end(start, end),
-        came_from: None,
-    };
-
-    open_set.insert(start, start_node);
-
-    while !open_set.is_empty() {
-        let current_coord = open_set
-            .iter()
-            .min_by(|a, b| {
-                a.1.f_score
-                    .partial_cmp(&b.1.f_score)
-                    .unwrap_or(Ordering::Equal)
-            })
-            .map(|(c, _)| *c)
-            .unwrap();
-
-        if current_coord == end {
-            return Some(reconstruct_path(current_coord, &came_from));
-        }
-
-        let current_node = open_set.remove(&current_coord).unwrap();
-        closed_set.insert(current_coord, current_node);
+// 2^14 cached evaluations per thread
+const EVALUATION_BITS: u32 = 14;
 
-        for neighb in current_coord.get_neighbours(b) {
-            if closed_set.contains_key(&neighb) {
-                continue;
-            }
-
-            let mut tentative_g = current_node.g_score + 1.0;
-
-            for snake in &b.snakes {
-                if snake.body.contains(&neighb) {
-                    tentative_g += 1000.0;
-                }
-            }
-
-            let neighbour_node = open_set.entry(neighb).or_insert(Node {
-                coord: neighb,
-                g_score: f32::INFINITY,
-                f_score: f32::INFINITY,
-                came_from: None,
-            });
-
-            if tentative_g < neighbour_node.g_score {
-                came_from.insert(neighb, current_coord);
-                neighbour_node.g_score = tentative_g;
-                neighbour_node.f_score = tentative_g + heuristic_from_n_to_end(neighb, end);
-            }
-        }
-    }
+thread_local! {
+    static PATH_FINDER: RefCell<PathFinder> = RefCell::new(PathFinder::new());
+    static EVALUATIONS: TranspositionTable = TranspositionTable::new(EVALUATION_BITS);
+}
 
-    None
+// the cost grid is only rebuilt when the board changes, so every path asked
+// for during a turn shares one load
+pub fn a_star_path_find(start: Coord, end: Coord, b: &Board) -> Option<Vec<Coord>> {
+    return PATH_FINDER.with(|pf| {
+        let mut pf = pf.borrow_mut();
+        pf.load(b);
+        pf.find(start, end, b)
+    });
 }
 
-pub fn heuristic_from_n_to_end(n: Coord, end: Coord) -> f32 {
-    return n.get_distance_to(&end) as f32;
+pub fn heuristic_from_n_to_end(n: Coord, end: Coord, b: &Board) -> f32 {
+    return n.get_distance_on(&end, b) as f32;
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -469,31 +406,53 @@ pub struct Board {
     food: Vec<Coord>,
     hazards: Vec<Coord>,
     snakes: Vec<Battlesnake>,
+    // not part of the payload, filled in from the ruleset
+    #[serde(skip)]
+    wrapped: bool,
+    #[serde(skip)]
+    hazard_damage: u16,
 }
 
 impl Board {
-    fn get_longest_snake_id(self: &Self) -> String {
-        let mut longest = &self.snakes[0];
-        if self.snakes.len() == 0 {
-            return longest.id.clone();
-        } else {
-            for s in &self.snakes {
-                if s.body.len() >= longest.body.len() {
-                    longest = &s;
-                }
+    fn get_longest_snake_id(self: &Self) -> Option<String> {
+        let mut longest = self.snakes.first()?;
+
+        for s in &self.snakes {
+            if s.body.len() >= longest.body.len() {
+                longest = s;
             }
         }
-        return longest.id.clone();
+
+        return Some(longest.id.clone());
     }
-    fn coord_has_snake(self: &Self, c: &Coord) -> bool {
-        for s in self.snakes.clone() {
-            for b in s.body {
-                if c.x == b.x && c.y == b.y {
-                    return true;
+
+    // a copy of the board with one snake advanced onto `to`, eating and
+    // growing by the normal rules. every other snake stays where it is
+    pub fn with_snake_moved(self: &Self, id: &str, to: Coord) -> Board {
+        let mut next = self.clone();
+        let ate = next.food.contains(&to);
+
+        if ate {
+            next.food.retain(|f| *f != to);
+        }
+
+        if let Some(s) = next.snakes.iter_mut().find(|s| s.id == id) {
+            s.body.insert(0, to);
+            s.body.pop();
+            s.head = to;
+
+            if ate {
+                if let Some(tail) = s.body.last() {
+                    s.body.push(*tail);
                 }
+                s.length += 1;
+                s.health = 100;
+            } else {
+                s.health = s.health.saturating_sub(1);
             }
         }
-        return false;
+
+        return next;
     }
 }
 
@@ -513,48 +472,32 @@ pub struct Battlesnake {
 
 impl Battlesnake {
     pub fn get_missing_health(self: &Self) -> u16 {
-        return 100 - self.health;
+        return 100u16.saturating_sub(self.health);
     }
 
     pub fn is_longest_snake_on_board(self: &Self, b: &Board) -> bool {
-        return b.get_longest_snake_id() == self.id;
+        return b.get_longest_snake_id().as_ref() == Some(&self.id);
     }
 
     pub fn ate_last_turn(self: &Self) -> bool {
-        return self.body[self.body.len() - 1] == self.body[self.body.len() - 2];
+        let len = self.body.len();
+        if len < 2 {
+            return false;
+        }
+
+        return self.body[len - 1] == self.body[len - 2];
     }
 
     pub fn avoid_own_neck(self: &Self, sm: &mut ScoredMoves, b: &Board) {
         let head = &self.head;
-        let neck = &self.body[1];
-
-        // neck is left of head
-
-        if head.x > 0 {
-            if head.x - 1 == neck.x && head.y == neck.y {
-                sm.Left.score = ScoredMoves::DEATH;
-            }
-        }
-
-        // neck is right of head
-
-        if head.x + 1 < b.width {
-            if head.x + 1 == neck.x && head.y == neck.y {
-                sm.Right.score = ScoredMoves::DEATH;
-            }
-        }
-
-        // neck is up from head
-
-        if head.y + 1 < b.height {
-            if head.x == neck.x && head.y == neck.y + 1 {
-                sm.Up.score = ScoredMoves::DEATH;
-            }
-        }
+        let neck = match self.body.get(1) {
+            Some(n) => n,
+            None => return,
+        };
 
-        if neck.y > 0 {
-            if head.x == neck.x && head.y == neck.y - 1 {
-                sm.Down.score = ScoredMoves::DEATH;
+        for dir in Direction::ALL {
+            if head.get_next_coord(dir, b) == Some(*neck) {
+                sm.kill(dir);
             }
         }
     }
@@ -562,54 +505,11 @@ impl Battlesnake {
     pub fn avoid_any_snake(self: &Self, sm: &mut ScoredMoves, b: &Board) {
         let head = &self.head;
 
-        // look left
-        if head.x > 0 {
-            let target: &Coord = &Coord {
-                x: head.x - 1,
-                y: head.y,
-            };
-
-            if target.is_in_snakeBody(b) {
-                sm.Left.score = ScoredMoves::DEATH;
-            }
-        }
-
-        // look down
-
-        if head.y > 0 {
-            let target: &Coord = &Coord {
-                x: head.x,
-                y: head.y - 1,
-            };
-
-            if target.is_in_snakeBody(b) {
-                sm.Down.score = ScoredMoves::DEATH;
-            }
-        }
-
-        // look right
-
-        if head.x + 1 < b.width {
-            let target: &Coord = &Coord {
-                x: head.x + 1,
-                y: head.y,
-            };
-
-            if target.is_in_snakeBody(b) {
-                sm.Right.score = ScoredMoves::DEATH;
-            }
-        }
-
-        // look up
-
-        if head.y + 1 < b.height {
-            let target: &Coord = &Coord {
-                x: head.x,
-                y: head.y + 1,
-            };
-
-            if target.is_in_snakeBody(b) {
-                sm.Up.score = ScoredMoves::DEATH;
+        for dir in Direction::ALL {
+            if let Some(target) = head.get_next_coord(dir, b)
+                && target.is_in_snakeBody(b)
+            {
+                sm.kill(dir);
             }
         }
     }
@@ -617,46 +517,11 @@ impl Battlesnake {
     pub fn avoid_walls(self: &Self, sm: &mut ScoredMoves, b: &Board) {
         let head = &self.head;
 
-        if head.x == 0 {
-            sm.Left.score = ScoredMoves::DEATH;
-        }
-
-        if head.x == b.width - 1 {
-            sm.Right.score = ScoredMoves::DEATH;
-        }
-
-        if head.y == 0 {
-            sm.Down.score = ScoredMoves::DEATH;
-        }
-
-        if head.y == b.height - 1 {
-            sm.Up.score = ScoredMoves::DEATH;
-        }
-    }
-
-    pub fn find_closest_food(self: &Self, b: &Board) -> Option<Coord> {
-        if b.food.len() == 0 {
-            return None;
-        } else {
-            // sort all foods on board by distance from head.
-
-            let mut distanceFoods: Vec<CoordWithDistance> = Vec::new();
-
-            for f in b.food.clone() {
-                let cwd = CoordWithDistance {
-                    x: f.x,
-                    y: f.y,
-                    distance: f.get_distance_to(&self.head),
-                };
-                distanceFoods.push(cwd);
+        // on a wrapped board there is always a next coord
+        for dir in Direction::ALL {
+            if head.get_next_coord(dir, b).is_none() {
+                sm.kill(dir);
             }
-
-            distanceFoods.sort();
-
-            return Some(Coord {
-                x: distanceFoods[0].x,
-                y: distanceFoods[0].y,
-            });
         }
     }
 
@@ -667,10 +532,16 @@ impl Battlesnake {
     }
 
     pub fn _follow_path(self: &Self, sm: &mut ScoredMoves, path: Vec<Coord>) {
-        let mut pathStart = path[0];
+        let mut pathStart = match path.first() {
+            Some(c) => *c,
+            None => return,
+        };
 
         if pathStart == self.head {
-            pathStart = path[1];
+            pathStart = match path.get(1) {
+                Some(c) => *c,
+                None => return,
+            };
         }
 
         let pathDirection = self.head.get_direction_to(&pathStart);
@@ -699,7 +570,10 @@ impl Battlesnake {
         // b: &Board,
         weight: i16,
     ) {
-        let mut pathStart = path[0];
+        let mut pathStart = match path.first() {
+            Some(c) => *c,
+            None => return,
+        };
 
         if pathStart == self.head && path.len() > 1 {
             pathStart = path[1];
@@ -725,45 +599,56 @@ impl Battlesnake {
     }
 
     pub fn move_toward_tail(self: &Self, sm: &mut ScoredMoves, b: &Board) {
-        let tail = self.body[self.body.len() - 1];
+        let tail = match self.body.last() {
+            Some(t) => *t,
+            None => return,
+        };
 
         let path = self.find_path_to(b, &tail);
 
-        if path.is_some() {
-            match path {
-                Some(p) => {
-                    // if path_is_clear(&p, b) {
-                    self.follow_path_with_weight(sm, p, 1);
-                    // }
-                }
-                None => {}
-            }
+        if let Some(p) = path {
+            // if path_is_clear(&p, b) {
+            self.follow_path_with_weight(sm, p, 1);
+            // }
         }
     }
 
-    pub fn move_toward_food(self: &Self, sm: &mut ScoredMoves, b: &Board) {
-        let cf = self.find_closest_food(b);
-        if cf.is_some() {
-            match cf {
-                Some(c) => {
-                    let path = self.find_path_to(b, &c);
+    pub fn move_toward_food(
+        self: &Self,
+        sm: &mut ScoredMoves,
+        b: &Board,
+        dm: &DistanceMap,
+        predictions: &MovePredictions,
+        risk: &RiskProfile,
+    ) {
+        // only chase food we'd actually get to first, and can get away from
+        for target in self.rank_food(b, dm, predictions, risk) {
+            let path = match self.find_path_to(b, &target.get_coord()) {
+                Some(p) => p,
+                None => continue,
+            };
 
-                    if path.is_some() {
-                        match path {
-                            Some(p) => {
-                                self.follow_path_with_weight(sm, p, 100 - self.health as i16);
-                            }
-                            None => {}
-                        }
-                    }
-                }
-                None => {}
+            if !self.has_escape_after_eating(b, &path) {
+                println!("[food {:?}]: no way out after eating", target.get_coord());
+                continue;
             }
+
+            self.follow_path_with_weight(sm, path, self.get_missing_health() as i16);
+            return;
         }
     }
 
-    pub fn _get_distance_toward_food(self: &Self, b: &Board) -> u16 {
-        let cf = self.find_closest_food(b);
+    pub fn _get_distance_toward_food(
+        self: &Self,
+        b: &Board,
+        dm: &DistanceMap,
+        predictions: &MovePredictions,
+        risk: &RiskProfile,
+    ) -> u16 {
+        let cf = self
+            .rank_food(b, dm, predictions, risk)
+            .first()
+            .map(|t| t.get_coord());
 
         if cf.is_some() {
             match cf {
@@ -773,7 +658,7 @@ impl Battlesnake {
                     if path.is_some() {
                         match path {
                             Some(p) => {
-                                return p.len().try_into().unwrap();
+                                return p.len().try_into().unwrap_or(u16::MAX);
                             }
                             None => {
                                 return 0;
@@ -795,185 +680,116 @@ impl Battlesnake {
     pub fn use_flood_fill(self: &Self, sm: &mut ScoredMoves, b: &Board) {
         let head = self.head;
 
-        //let safe_neighbours = head.get_neighbours(b);
-
-        if head.x > 0 {
-            let lv = flood_fill(
-                b,
-                Coord {
-                    x: head.x - 1,
-                    y: head.y,
-                },
-            );
-            sm.Left.score += lv;
-            println!("[ff {}]: {}", "left", lv)
-        }
-
-        if head.x + 1 < b.width {
-            let rv = flood_fill(
-                b,
-                Coord {
-                    x: head.x + 1,
-                    y: head.y,
-                },
-            );
-            sm.Right.score += rv;
-            println!("[ff {}]: {}", "right", rv)
-        }
-
-        if head.y > 0 {
-            let dv = flood_fill(
-                b,
-                Coord {
-                    x: head.x,
-                    y: head.y - 1,
-                },
-            );
-            sm.Down.score += dv;
-            println!("[ff {}]: {}", "down", dv)
-        }
-
-        if head.y + 1 < b.height {
-            let uv = flood_fill(
-                b,
-                Coord {
-                    x: head.x,
-                    y: head.y + 1,
-                },
-            );
-            sm.Up.score += uv;
-            println!("[ff {}]: {}", "up", uv)
+        for dir in Direction::ALL {
+            if let Some(target) = head.get_next_coord(dir, b) {
+                let v = flood_fill(b, target);
+                sm.get_mut(dir).score += v;
+                println!("[ff {}]: {}", dir.get_str(), v)
+            }
         }
     }
 
-    pub fn attack_smaller_snake_head(self: &Self, sm: &mut ScoredMoves, b: &Board) {
-        const SMALL_SNAKE_ATTACK: i16 = 75;
+    pub fn avoid_larger_snake_head(
+        self: &Self,
+        sm: &mut ScoredMoves,
+        b: &Board,
+        predictions: &MovePredictions,
+        risk: &RiskProfile,
+    ) {
+        const LARGE_SNAKE_AVOIDANCE: i16 = -100;
         // look each possible direction, if that move is scored >= 0
 
-        // left
-
-        if sm.Left.score >= 0 {
-            // get new head position after move
-            let newHead = self.head.get_next_coord(sm.Left.direction);
-            // get all neighbours of new head position
-            let nbs = newHead.get_neighbours(b);
-
-            for n in nbs {
-                if n.contains_smaller_snake_head(self, b) {
-                    sm.Left.score += SMALL_SNAKE_ATTACK;
-                }
+        for dir in Direction::ALL {
+            if sm.get(dir).score < 0 {
+                continue;
             }
-        }
 
-        // right
-
-        if sm.Right.score >= 0 {
             // get new head position after move
-            let newHead = self.head.get_next_coord(sm.Right.direction);
-
-            let nbs = newHead.get_neighbours(b);
-
-            for n in nbs {
-                if n.contains_smaller_snake_head(self, b) {
-                    sm.Right.score += SMALL_SNAKE_ATTACK;
-                }
+            if let Some(newHead) = self.head.get_next_coord(dir, b) {
+                sm.get_mut(dir).score += self.score_nearby_heads(
+                    &newHead,
+                    b,
+                    predictions,
+                    risk,
+                    LARGE_SNAKE_AVOIDANCE,
+                    true,
+                );
             }
         }
+    }
 
-        // up
-
-        if sm.Up.score >= 0 {
-            // get new head position after move
-            let newHead = self.head.get_next_coord(sm.Up.direction);
-
-            let nbs = newHead.get_neighbours(b);
-
-            for n in nbs {
-                if n.contains_smaller_snake_head(self, b) {
-                    sm.Up.score += SMALL_SNAKE_ATTACK;
+    // each opponent head next to `target` contributes `per_head`, scaled by how
+    // likely that snake is to step into `target` compared to a random move.
+    // the worst case is that it certainly does
+    fn score_nearby_heads(
+        self: &Self,
+        target: &Coord,
+        b: &Board,
+        predictions: &MovePredictions,
+        risk: &RiskProfile,
+        per_head: i16,
+        larger: bool,
+    ) -> i16 {
+        let mut total: f32 = 0.0;
+
+        for n in target.get_neighbours(b) {
+            for snake in &b.snakes {
+                if snake.id == self.id || snake.head != n {
+                    continue;
                 }
-            }
-        }
-
-        // down
-
-        if sm.Down.score >= 0 {
-            // get new head position after move
-            let newHead = self.head.get_next_coord(sm.Down.direction);
 
-            let nbs = newHead.get_neighbours(b);
-
-            for n in nbs {
-                if n.contains_smaller_snake_head(self, b) {
-                    sm.Down.score += SMALL_SNAKE_ATTACK;
+                if (snake.length >= self.length) == larger {
+                    let weight = risk.blend(
+                        Decision::Heads,
+                        predictions.worst_case_weight(&snake.id, target),
+                        predictions.weight(&snake.id, target),
+                    );
+                    total += per_head as f32 * weight;
                 }
             }
         }
-    }
 
-    pub fn avoid_larger_snake_head(self: &Self, sm: &mut ScoredMoves, b: &Board) {
-        const LARGE_SNAKE_AVOIDANCE: i16 = -100;
-        // look each possible direction, if that move is scored >= 0
+        return total.round() as i16;
+    }
 
-        // left
+    // used when scoring failed part way. only looks at walls and bodies, and
+    // prefers the open cell with the most room around it
+    pub fn get_fallback_move(self: &Self, b: &Board) -> Direction {
+        let mut best: Option<(Direction, usize)> = None;
 
-        if sm.Left.score >= 0 {
-            // get new head position after move
-            let newHead = self.head.get_next_coord(sm.Left.direction);
-            // get all neighbours of new head position
-            let nbs = newHead.get_neighbours(b);
+        for dir in Direction::ALL {
+            let next = match self.head.get_next_coord(dir, b) {
+                Some(c) => c,
+                None => continue,
+            };
 
-            for n in nbs {
-                if n.contains_larger_snake_head(self, b) {
-                    sm.Left.score += LARGE_SNAKE_AVOIDANCE;
-                }
+            if next.is_in_snakeBody(b) {
+                continue;
             }
-        }
 
-        // right
+            let room = next
+                .get_neighbours(b)
+                .iter()
+                .filter(|n| !n.is_in_snakeBody(b))
+                .count();
 
-        if sm.Right.score >= 0 {
-            // get new head position after move
-            let newHead = self.head.get_next_coord(sm.Right.direction);
-
-            let nbs = newHead.get_neighbours(b);
-
-            for n in nbs {
-                if n.contains_larger_snake_head(self, b) {
-                    sm.Right.score += LARGE_SNAKE_AVOIDANCE;
-                }
+            if best.is_none_or(|(_, r)| room > r) {
+                best = Some((dir, room));
             }
         }
 
-        // up
-
-        if sm.Up.score >= 0 {
-            // get new head position after move
-            let newHead = self.head.get_next_coord(sm.Up.direction);
-
-            let nbs = newHead.get_neighbours(b);
-
-            for n in nbs {
-                if n.contains_larger_snake_head(self, b) {
-                    sm.Up.score += LARGE_SNAKE_AVOIDANCE;
-                }
-            }
+        if let Some((dir, _)) = best {
+            return dir;
         }
 
-        // down
-
-        if sm.Down.score >= 0 {
-            // get new head position after move
-            let newHead = self.head.get_next_coord(sm.Down.direction);
-
-            let nbs = newHead.get_neighbours(b);
-
-            for n in nbs {
-                if n.contains_larger_snake_head(self, b) {
-                    sm.Down.score += LARGE_SNAKE_AVOIDANCE;
-                }
+        // nothing is safe, at least stay on the board
+        for dir in Direction::ALL {
+            if self.head.get_next_coord(dir, b).is_some() {
+                return dir;
             }
         }
+
+        return Direction::Up;
     }
 
     pub fn choose_move(self: &Self, sm: &ScoredMoves) -> ScoredMove {
@@ -988,17 +804,11 @@ impl Battlesnake {
 
         possible_moves.sort_by(|a, b| b.cmp(a));
 
-        if possible_moves[0].score == possible_moves[1].score {
-            return ScoredMove {
-                direction: possible_moves[1].direction,
-                score: possible_moves[1].score,
-            };
+        if possible_moves[0].cmp(possible_moves[1]).is_eq() {
+            return *possible_moves[1];
         }
 
-        return ScoredMove {
-            direction: possible_moves[0].direction,
-            score: possible_moves[0].score,
-        };
+        return *possible_moves[0];
     }
 }
 
@@ -1011,6 +821,33 @@ pub struct MoveRequest {
 }
 
 impl MoveRequest {
+    // board settings that only come through on the game object
+    pub fn apply_ruleset(self: &mut Self) {
+        self.board.wrapped = self.game.ruleset.name == "wrapped";
+        self.board.hazard_damage = self.game.ruleset.settings.hazardDamagePerTurn;
+    }
+
+    pub fn get_game_id(self: &Self) -> String {
+        return self.game.id.clone();
+    }
+
+    pub fn get_turn(self: &Self) -> u16 {
+        return self.turn;
+    }
+
+    pub fn get_ruleset_name(self: &Self) -> &str {
+        return &self.game.ruleset.name;
+    }
+
+    // milliseconds we have to answer in, including the trip over the network
+    pub fn get_timeout(self: &Self) -> u16 {
+        return self.game.timeout;
+    }
+
+    pub fn set_timeout(self: &mut Self, ms: u16) {
+        self.game.timeout = ms;
+    }
+
     pub fn get_board_ref(self: &Self) -> &Board {
         return &self.board;
     }
@@ -1028,6 +865,12 @@ pub struct GameOver {
     you: Battlesnake,
 }
 
+impl GameOver {
+    pub fn get_game_id(self: &Self) -> String {
+        return self.game.id.clone();
+    }
+}
+
 // #[derive(Debug, Serialize, Clone)]
 // struct MoveResponse {
 //     move: String,
@@ -1066,3 +909,121 @@ impl BattlesnakeDetails {
         return bd;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::{Fixture, snake};
+
+    // a wall down the middle of a 3x3 board whose far end leaves after
+    // `tail` extra turns
+    fn walled(tail: usize) -> Board {
+        let mut body = vec![(1, 0), (1, 1), (1, 2)];
+        body.extend(std::iter::repeat_n((2, 2), tail));
+
+        return Fixture::new(3, 3, vec![snake("wall", &body, 90)]).board();
+    }
+
+    #[test]
+    fn a_wall_that_moves_in_time_is_no_wall() {
+        let b = walled(0);
+        assert_eq!(flood_fill(&b, Coord { x: 0, y: 1 }), 9);
+    }
+
+    #[test]
+    fn a_wall_that_outlasts_the_pocket_seals_it() {
+        let b = walled(10);
+        assert_eq!(flood_fill(&b, Coord { x: 0, y: 1 }), 3);
+        // the stacked tail sits in the other pocket's corner
+        assert_eq!(flood_fill(&b, Coord { x: 2, y: 0 }), 2);
+    }
+
+    #[test]
+    fn nothing_is_reached_from_a_blocked_start() {
+        let b = walled(10);
+        assert_eq!(flood_fill(&b, Coord { x: 1, y: 1 }), 0);
+        assert_eq!(flood_fill(&b, Coord { x: 3, y: 1 }), 0);
+    }
+
+    #[test]
+    fn cached_fills_match_fresh_ones() {
+        for tail in [0, 1, 2, 10] {
+            let b = walled(tail);
+            for c in [Coord { x: 0, y: 0 }, Coord { x: 2, y: 1 }] {
+                let fresh = count_flood_fill(&b, c);
+                assert_eq!(flood_fill(&b, c), fresh);
+                assert_eq!(flood_fill(&b, c), fresh);
+            }
+        }
+    }
+
+    #[test]
+    fn the_fallback_takes_the_roomiest_safe_move() {
+        // down is a wall and left is our neck. up and right are both open,
+        // but b's body hems in up
+        let f = Fixture::new(
+            5,
+            5,
+            vec![
+                snake("me", &[(1, 0), (0, 0)], 90),
+                snake("b", &[(0, 1), (0, 2), (1, 2), (2, 2), (2, 1)], 90),
+            ],
+        );
+
+        assert_eq!(f.you().get_fallback_move(&f.board()), Direction::Right);
+    }
+
+    #[test]
+    fn with_nothing_safe_the_fallback_stays_on_the_board() {
+        let f = Fixture::new(
+            2,
+            2,
+            vec![
+                snake("me", &[(0, 0), (1, 0)], 90),
+                snake("b", &[(0, 1), (1, 1), (1, 1)], 90),
+            ],
+        );
+
+        let dir = f.you().get_fallback_move(&f.board());
+        assert!(matches!(dir, Direction::Right | Direction::Up));
+    }
+
+    #[test]
+    fn short_and_empty_bodies_are_handled() {
+        let f = Fixture::new(5, 5, vec![snake("me", &[(2, 2)], 120)]);
+        let b = f.board();
+        let mut me = f.you();
+
+        assert!(!me.ate_last_turn());
+        assert_eq!(me.get_missing_health(), 0);
+
+        let mut sm = ScoredMoves::init();
+        me.avoid_own_neck(&mut sm, &b);
+        assert_eq!(sm.get_alive_moves().len(), 4);
+
+        me.body.clear();
+        me.move_toward_tail(&mut sm, &b);
+        assert!(!me.ate_last_turn());
+    }
+
+    #[test]
+    fn nobody_is_longest_on_an_empty_board() {
+        let f = Fixture::new(5, 5, vec![snake("me", &[(2, 2), (2, 1)], 90)]);
+        let mut b = f.board();
+        assert!(f.you().is_longest_snake_on_board(&b));
+
+        b.snakes.clear();
+        assert!(!f.you().is_longest_snake_on_board(&b));
+    }
+
+    #[test]
+    fn negative_coordinates_read_as_off_the_board() {
+        let f = Fixture::new(5, 5, vec![snake("me", &[(-1, 2), (0, 2)], 90)]);
+        let b = f.board();
+        let me = f.you();
+
+        assert_eq!(me.head, Coord { x: u16::MAX, y: 2 });
+        assert!(!me.head.is_in_bounds(&b));
+        assert_eq!(me.head.get_next_coord(Direction::Right, &b), None);
+    }
+}
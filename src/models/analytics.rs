@@ -0,0 +1,429 @@
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::GameOver;
+use super::opponents::GameHistory;
+
+const DEFAULT_RESULTS_PATH: &str = "results.jsonl";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum GameResult {
+    Win,
+    Loss,
+    Draw,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DeathCause {
+    Wall,
+    OwnBody,
+    Body,
+    HeadToHead,
+    Starvation,
+    Hazard,
+    Unknown,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GameRecord {
+    game_id: String,
+    ruleset: String,
+    turn: u16,
+    length: u16,
+    result: GameResult,
+    cause: Option<DeathCause>,
+}
+
+impl GameOver {
+    // the turn we were eliminated on: one after the last move request we
+    // answered, if we saw any of this game
+    fn get_death_turn(self: &Self, history: Option<&GameHistory>) -> u16 {
+        return match history.and_then(|h| h.get_last_turn()) {
+            Some(t) => t + 1,
+            None => self.turn,
+        };
+    }
+
+    pub fn get_result(self: &Self, history: Option<&GameHistory>) -> GameResult {
+        let alive = self.board.snakes.iter().any(|s| s.id == self.you.id);
+
+        if alive {
+            return GameResult::Win;
+        }
+
+        // everyone left on the board died on the same turn as us, unless we
+        // were playing alone
+        if self.board.snakes.is_empty()
+            && self.game.ruleset.name != "solo"
+            && self.get_death_turn(history) >= self.turn
+        {
+            return GameResult::Draw;
+        }
+
+        return GameResult::Loss;
+    }
+
+    // `you` holds our body after the move that killed us, but the final board
+    // can be many turns later. collisions are judged against the last board we
+    // were alive on, in the order the rules apply them
+    pub fn get_death_cause(self: &Self, history: Option<&GameHistory>) -> Option<DeathCause> {
+        if self.get_result(history) == GameResult::Win {
+            return None;
+        }
+
+        let me = &self.you;
+        let head = me.head;
+        let wrapped = self.game.ruleset.name == "wrapped";
+        let last = history.and_then(|h| h.get_last_board());
+
+        // out of health snakes are removed before anything is moved into.
+        // hazards can spread after we die, so they're the ones we moved onto
+        if me.health == 0 {
+            let hazards = &last.unwrap_or(&self.board).hazards;
+            if hazards.contains(&head) {
+                return Some(DeathCause::Hazard);
+            }
+            return Some(DeathCause::Starvation);
+        }
+
+        if !wrapped && !head.is_in_bounds(&self.board) {
+            return Some(DeathCause::Wall);
+        }
+
+        if me.body.iter().skip(1).any(|c| *c == head) {
+            return Some(DeathCause::OwnBody);
+        }
+
+        match last {
+            Some(last) => {
+                let length = match last.snakes.iter().find(|s| s.id == me.id) {
+                    Some(s) => s.length,
+                    None => me.length,
+                };
+                let others = last.snakes.iter().filter(|s| s.id != me.id);
+
+                for other in others.clone() {
+                    // a tail moves out of the way unless the snake just ate
+                    let keep = if other.ate_last_turn() {
+                        other.body.len()
+                    } else {
+                        other.body.len().saturating_sub(1)
+                    };
+
+                    if other.body[..keep].contains(&head) {
+                        return Some(DeathCause::Body);
+                    }
+                }
+
+                for other in others {
+                    if other.length >= length && other.head.get_distance_on(&head, last) == 1 {
+                        return Some(DeathCause::HeadToHead);
+                    }
+                }
+            }
+            None => {
+                for other in &self.board.snakes {
+                    if other.head == head {
+                        return Some(DeathCause::HeadToHead);
+                    }
+                }
+
+                for other in &self.board.snakes {
+                    if other.body.contains(&head) {
+                        return Some(DeathCause::Body);
+                    }
+                }
+            }
+        }
+
+        return Some(DeathCause::Unknown);
+    }
+
+    // `history` is what we saw of the game through its move requests
+    pub fn get_record(self: &Self, history: Option<&GameHistory>) -> GameRecord {
+        let result = self.get_result(history);
+        let turn = match result {
+            GameResult::Win => self.turn,
+            _ => self.get_death_turn(history),
+        };
+
+        return GameRecord {
+            game_id: self.game.id.clone(),
+            ruleset: self.game.ruleset.name.clone(),
+            turn,
+            length: self.you.length,
+            result,
+            cause: self.get_death_cause(history),
+        };
+    }
+}
+
+pub fn results_path() -> String {
+    return std::env::var("BATTLESNAKE_RESULTS").unwrap_or(DEFAULT_RESULTS_PATH.to_string());
+}
+
+pub fn append_record(path: &str, record: &GameRecord) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let line = serde_json::to_string(record)?;
+    writeln!(file, "{}", line)?;
+    return Ok(());
+}
+
+pub fn load_records(path: &str) -> std::io::Result<Vec<GameRecord>> {
+    let file = OpenOptions::new().read(true).open(path)?;
+    let mut records: Vec<GameRecord> = Vec::new();
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // skip lines we can't read rather than losing the whole report
+        match serde_json::from_str::<GameRecord>(&line) {
+            Ok(r) => records.push(r),
+            Err(e) => eprintln!("skipping bad results line: {}", e),
+        }
+    }
+
+    return Ok(records);
+}
+
+pub fn report(records: &[GameRecord], last_n: usize) -> String {
+    let start = records.len().saturating_sub(last_n);
+    let recent = &records[start..];
+
+    let mut out = String::new();
+
+    if recent.is_empty() {
+        out.push_str("no games recorded\n");
+        return out;
+    }
+
+    let count = |r: GameResult| recent.iter().filter(|g| g.result == r).count();
+    let wins = count(GameResult::Win);
+    let losses = count(GameResult::Loss);
+    let draws = count(GameResult::Draw);
+
+    out.push_str(&format!("last {} games\n", recent.len()));
+    out.push_str(&format!(
+        "  wins: {}  losses: {}  draws: {}  win rate: {:.1}%\n",
+        wins,
+        losses,
+        draws,
+        100.0 * wins as f32 / recent.len() as f32
+    ));
+
+    let causes = [
+        DeathCause::Wall,
+        DeathCause::OwnBody,
+        DeathCause::Body,
+        DeathCause::HeadToHead,
+        DeathCause::Starvation,
+        DeathCause::Hazard,
+        DeathCause::Unknown,
+    ];
+
+    let mut counted: Vec<(DeathCause, usize, f32)> = causes
+        .iter()
+        .map(|c| {
+            let games: Vec<&GameRecord> = recent.iter().filter(|g| g.cause == Some(*c)).collect();
+            let avg_turn = if games.is_empty() {
+                0.0
+            } else {
+                games.iter().map(|g| g.turn as f32).sum::<f32>() / games.len() as f32
+            };
+            (*c, games.len(), avg_turn)
+        })
+        .filter(|(_, n, _)| *n > 0)
+        .collect();
+
+    counted.sort_by_key(|c| std::cmp::Reverse(c.1));
+
+    let deaths: usize = counted.iter().map(|(_, n, _)| n).sum();
+
+    out.push_str("death causes\n");
+    if counted.is_empty() {
+        out.push_str("  none\n");
+    }
+    for (cause, n, avg_turn) in counted {
+        out.push_str(&format!(
+            "  {:<12} {:>4} ({:>5.1}%)  avg turn {:.0}\n",
+            format!("{:?}", cause),
+            n,
+            100.0 * n as f32 / deaths as f32,
+            avg_turn
+        ));
+    }
+
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    // the game as we last saw it on `turn`, then the /end payload arriving
+    // on `end_turn` with `you` after the move that killed us
+    fn ended(
+        last: Fixture,
+        turn: u16,
+        end: Fixture,
+        end_turn: u16,
+        you: serde_json::Value,
+    ) -> (GameHistory, GameOver) {
+        let mut history = GameHistory::default();
+        let last = last.with_turn(turn);
+        history.observe(turn, &last.board(), &last.you());
+        return (history, end.with_turn(end_turn).game_over(you));
+    }
+
+    fn cause(last: Fixture, you: serde_json::Value) -> Option<DeathCause> {
+        let end = Fixture::new(last.width, last.height, vec![snake("b", &[(9, 9)], 90)]);
+        let (history, over) = ended(last, 10, end, 11, you);
+        return over.get_death_cause(Some(&history));
+    }
+
+    #[test]
+    fn starvation_is_checked_before_collisions() {
+        let last = Fixture::new(11, 11, vec![snake("me", &[(0, 5), (1, 5), (2, 5)], 1)]);
+        let you = snake("me", &[(-1, 5), (0, 5), (1, 5)], 0);
+        assert_eq!(cause(last, you), Some(DeathCause::Starvation));
+
+        let last = Fixture::new(
+            11,
+            11,
+            vec![
+                snake("me", &[(4, 4), (4, 3), (4, 2)], 1),
+                snake("b", &[(6, 5), (5, 5), (4, 5), (3, 5)], 80),
+            ],
+        );
+        let you = snake("me", &[(4, 5), (4, 4), (4, 3)], 0);
+        assert_eq!(cause(last, you), Some(DeathCause::Starvation));
+    }
+
+    #[test]
+    fn own_body() {
+        let body = [(2, 2), (3, 2), (3, 3), (2, 3), (1, 3)];
+        let last = Fixture::new(11, 11, vec![snake("me", &body, 80)]);
+        let you = snake("me", &[(2, 3), (2, 2), (3, 2), (3, 3), (2, 3)], 79);
+        assert_eq!(cause(last, you), Some(DeathCause::OwnBody));
+    }
+
+    #[test]
+    fn body_of_another_snake() {
+        let last = Fixture::new(
+            11,
+            11,
+            vec![
+                snake("me", &[(4, 4), (4, 3), (4, 2)], 80),
+                snake("b", &[(6, 5), (5, 5), (4, 5), (3, 5)], 80),
+            ],
+        );
+        let you = snake("me", &[(4, 5), (4, 4), (4, 3)], 79);
+        assert_eq!(cause(last, you), Some(DeathCause::Body));
+    }
+
+    #[test]
+    fn moving_into_a_vacated_tail_is_not_a_body_collision() {
+        let last = Fixture::new(
+            11,
+            11,
+            vec![
+                snake("me", &[(4, 4), (4, 3), (4, 2)], 80),
+                snake("b", &[(6, 5), (5, 5), (4, 5)], 80),
+            ],
+        );
+        let you = snake("me", &[(4, 5), (4, 4), (4, 3)], 79);
+        assert_eq!(cause(last, you), Some(DeathCause::Unknown));
+    }
+
+    #[test]
+    fn head_to_head_with_a_longer_snake() {
+        let last = Fixture::new(
+            11,
+            11,
+            vec![
+                snake("me", &[(4, 4), (4, 3), (4, 2)], 80),
+                snake("b", &[(5, 5), (6, 5), (7, 5), (8, 5)], 80),
+            ],
+        );
+        let you = snake("me", &[(4, 5), (4, 4), (4, 3)], 79);
+        assert_eq!(cause(last, you), Some(DeathCause::HeadToHead));
+    }
+
+    #[test]
+    fn starvation_and_hazard() {
+        let last = Fixture::new(11, 11, vec![snake("me", &[(4, 4), (4, 3), (4, 2)], 1)]);
+        let you = snake("me", &[(4, 5), (4, 4), (4, 3)], 0);
+        assert_eq!(cause(last, you.clone()), Some(DeathCause::Starvation));
+
+        let last = Fixture::new(11, 11, vec![snake("me", &[(4, 4), (4, 3), (4, 2)], 10)])
+            .with_hazards(&[(4, 5)]);
+        assert_eq!(cause(last, you.clone()), Some(DeathCause::Hazard));
+    }
+
+    #[test]
+    fn hazards_that_spread_after_we_died_are_not_the_cause() {
+        let last = Fixture::new(11, 11, vec![snake("me", &[(4, 4), (4, 3), (4, 2)], 1)]);
+        let you = snake("me", &[(4, 5), (4, 4), (4, 3)], 0);
+        let end = Fixture::new(11, 11, vec![snake("b", &[(9, 9)], 90)]).with_hazards(&[(4, 5)]);
+        let (history, over) = ended(last, 10, end, 30, you);
+        assert_eq!(
+            over.get_death_cause(Some(&history)),
+            Some(DeathCause::Starvation)
+        );
+    }
+
+    #[test]
+    fn an_early_death_is_a_loss_recorded_on_the_turn_we_died() {
+        let last = Fixture::new(11, 11, vec![snake("me", &[(0, 5), (1, 5), (2, 5)], 80)]);
+        let you = snake("me", &[(-1, 5), (0, 5), (1, 5)], 79);
+
+        // everyone else died later, leaving the board empty at the end
+        let end = Fixture::new(11, 11, Vec::new());
+        let (history, over) = ended(last, 10, end, 40, you);
+        let record = over.get_record(Some(&history));
+
+        assert_eq!(record.result, GameResult::Loss);
+        assert_eq!(record.turn, 11);
+        assert_eq!(record.cause, Some(DeathCause::Wall));
+    }
+
+    #[test]
+    fn dying_together_on_the_last_turn_is_a_draw() {
+        let last = Fixture::new(
+            11,
+            11,
+            vec![
+                snake("me", &[(4, 4), (4, 3), (4, 2)], 80),
+                snake("b", &[(5, 5), (6, 5), (7, 5)], 80),
+            ],
+        );
+        let you = snake("me", &[(4, 5), (4, 4), (4, 3)], 79);
+        let end = Fixture::new(11, 11, Vec::new());
+        let (history, over) = ended(last, 10, end, 11, you);
+
+        assert_eq!(over.get_result(Some(&history)), GameResult::Draw);
+        assert_eq!(
+            over.get_death_cause(Some(&history)),
+            Some(DeathCause::HeadToHead)
+        );
+    }
+
+    #[test]
+    fn surviving_to_the_end_is_a_win() {
+        let board = Fixture::new(11, 11, vec![snake("me", &[(4, 4), (4, 3), (4, 2)], 80)]);
+        let over = board
+            .with_turn(50)
+            .game_over(snake("me", &[(4, 4), (4, 3), (4, 2)], 80));
+        let record = over.get_record(None);
+
+        assert_eq!(record.result, GameResult::Win);
+        assert_eq!(record.turn, 50);
+        assert_eq!(record.cause, None);
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+
+use super::{Battlesnake, Board, Coord, Direction, ScoredMoves};
+
+const DEFAULT_BOOK_PATH: &str = "opening_book.txt";
+// past this the positions stop repeating between games
+pub const BOOK_TURNS: u16 = 10;
+// square boards with the standard spawn points
+const BOOK_SIZES: [u16; 3] = [7, 11, 19];
+const BOOK_WEIGHT: i16 = 5000;
+
+// one of the eight ways to rotate or mirror a square board. the swap of x
+// and y happens first, then the flips
+#[derive(Copy, Clone, Debug)]
+struct Symmetry {
+    swap: bool,
+    flip_x: bool,
+    flip_y: bool,
+}
+
+const SYMMETRIES: [Symmetry; 8] = [
+    Symmetry {
+        swap: false,
+        flip_x: false,
+        flip_y: false,
+    },
+    Symmetry {
+        swap: false,
+        flip_x: true,
+        flip_y: false,
+    },
+    Symmetry {
+        swap: false,
+        flip_x: false,
+        flip_y: true,
+    },
+    Symmetry {
+        swap: false,
+        flip_x: true,
+        flip_y: true,
+    },
+    Symmetry {
+        swap: true,
+        flip_x: false,
+        flip_y: false,
+    },
+    Symmetry {
+        swap: true,
+        flip_x: true,
+        flip_y: false,
+    },
+    Symmetry {
+        swap: true,
+        flip_x: false,
+        flip_y: true,
+    },
+    Symmetry {
+        swap: true,
+        flip_x: true,
+        flip_y: true,
+    },
+];
+
+fn to_delta(dir: Direction) -> (i8, i8) {
+    return match dir {
+        Direction::Left => (-1, 0),
+        Direction::Right => (1, 0),
+        Direction::Down => (0, -1),
+        Direction::Up => (0, 1),
+    };
+}
+
+// symmetries only ever swap or negate a step, so anything else is a bug in
+// the transform
+fn from_delta(delta: (i8, i8)) -> Direction {
+    return match delta {
+        (-1, 0) => Direction::Left,
+        (1, 0) => Direction::Right,
+        (0, -1) => Direction::Down,
+        (0, 1) => Direction::Up,
+        _ => unreachable!("{:?} is not a single step", delta),
+    };
+}
+
+fn parse_direction(s: &str) -> Option<Direction> {
+    return Direction::ALL.into_iter().find(|d| d.get_str() == s);
+}
+
+impl Symmetry {
+    fn apply(self: &Self, c: &Coord, size: u16) -> Coord {
+        let (mut x, mut y) = match self.swap {
+            true => (c.y, c.x),
+            false => (c.x, c.y),
+        };
+
+        if self.flip_x {
+            x = size - 1 - x;
+        }
+        if self.flip_y {
+            y = size - 1 - y;
+        }
+
+        return Coord { x, y };
+    }
+
+    fn apply_dir(self: &Self, dir: Direction) -> Direction {
+        let (mut dx, mut dy) = to_delta(dir);
+        if self.swap {
+            (dx, dy) = (dy, dx);
+        }
+        if self.flip_x {
+            dx = -dx;
+        }
+        if self.flip_y {
+            dy = -dy;
+        }
+        return from_delta((dx, dy));
+    }
+
+    fn invert_dir(self: &Self, dir: Direction) -> Direction {
+        let (mut dx, mut dy) = to_delta(dir);
+        if self.flip_x {
+            dx = -dx;
+        }
+        if self.flip_y {
+            dy = -dy;
+        }
+        if self.swap {
+            (dx, dy) = (dy, dx);
+        }
+        return from_delta((dx, dy));
+    }
+
+    // the position as text seen through this symmetry. opponents and food
+    // are sorted so the order they came in doesn't matter
+    fn encode(self: &Self, b: &Board, you: &Battlesnake, ruleset: &str) -> String {
+        let cells = |body: &[Coord]| -> String {
+            return body
+                .iter()
+                .map(|c| {
+                    let t = self.apply(c, b.width);
+                    format!("{},{}", t.x, t.y)
+                })
+                .collect::<Vec<String>>()
+                .join(" ");
+        };
+
+        let mut opponents: Vec<String> = b
+            .snakes
+            .iter()
+            .filter(|s| s.id != you.id)
+            .map(|s| cells(&s.body))
+            .collect();
+        opponents.sort();
+
+        let mut food: Vec<String> = b.food.iter().map(|f| cells(&[*f])).collect();
+        food.sort();
+
+        return format!(
+            "{}|{}|{}|{}|{}",
+            ruleset,
+            b.width,
+            cells(&you.body),
+            opponents.join(";"),
+            food.join(" ")
+        );
+    }
+}
+
+// the same key for every rotation and reflection of a position in games of
+// one ruleset, along with the symmetry that turns the real board into the
+// stored one. None for positions the book doesn't cover
+fn normalise(b: &Board, you: &Battlesnake, turn: u16, ruleset: &str) -> Option<(String, Symmetry)> {
+    if turn > BOOK_TURNS
+        || b.wrapped
+        || !b.hazards.is_empty()
+        || b.width != b.height
+        || !BOOK_SIZES.contains(&b.width)
+    {
+        return None;
+    }
+
+    // anything off the board can't be mirrored
+    let on_board = b
+        .snakes
+        .iter()
+        .flat_map(|s| s.body.iter())
+        .chain(b.food.iter())
+        .all(|c| c.is_in_bounds(b));
+    if !on_board {
+        return None;
+    }
+
+    return SYMMETRIES
+        .iter()
+        .map(|s| (s.encode(b, you, ruleset), *s))
+        .min_by(|a, b| a.0.cmp(&b.0));
+}
+
+pub fn book_path() -> String {
+    return std::env::var("BATTLESNAKE_BOOK").unwrap_or(DEFAULT_BOOK_PATH.to_string());
+}
+
+// our move for known opening positions, one "key<TAB>move" per line
+#[derive(Default)]
+pub struct OpeningBook {
+    moves: HashMap<String, Direction>,
+}
+
+impl OpeningBook {
+    pub fn load(path: &str) -> std::io::Result<OpeningBook> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        let mut book = OpeningBook::default();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let parsed = line
+                .split_once('\t')
+                .and_then(|(key, dir)| Some((key, parse_direction(dir.trim())?)));
+
+            match parsed {
+                Some((key, dir)) => {
+                    book.moves.insert(key.to_string(), dir);
+                }
+                None if line.trim().is_empty() => {}
+                None => eprintln!("skipping bad book line: {}", line),
+            }
+        }
+
+        return Ok(book);
+    }
+
+    // read once when the server starts. a missing book just means every
+    // move is worked out
+    pub fn load_or_empty(path: &str) -> OpeningBook {
+        return match OpeningBook::load(path) {
+            Ok(book) => book,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    eprintln!("[error] could not read opening book {}: {}", path, e);
+                }
+                OpeningBook::default()
+            }
+        };
+    }
+
+    pub fn save(self: &Self, path: &str) -> std::io::Result<()> {
+        let mut lines: Vec<String> = self
+            .moves
+            .iter()
+            .map(|(key, dir)| format!("{}\t{}", key, dir.get_str()))
+            .collect();
+        lines.sort();
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        for line in lines {
+            writeln!(file, "{}", line)?;
+        }
+
+        return Ok(());
+    }
+
+    pub fn len(self: &Self) -> usize {
+        return self.moves.len();
+    }
+
+    pub fn covers(self: &Self, b: &Board, you: &Battlesnake, turn: u16, ruleset: &str) -> bool {
+        return normalise(b, you, turn, ruleset)
+            .is_some_and(|(key, _)| self.moves.contains_key(&key));
+    }
+
+    pub fn lookup(
+        self: &Self,
+        b: &Board,
+        you: &Battlesnake,
+        turn: u16,
+        ruleset: &str,
+    ) -> Option<Direction> {
+        let (key, symmetry) = normalise(b, you, turn, ruleset)?;
+        let stored = self.moves.get(&key)?;
+        return Some(symmetry.invert_dir(*stored));
+    }
+
+    // false when the position is outside the book
+    pub fn insert(
+        self: &mut Self,
+        b: &Board,
+        you: &Battlesnake,
+        turn: u16,
+        ruleset: &str,
+        dir: Direction,
+    ) -> bool {
+        let (key, symmetry) = match normalise(b, you, turn, ruleset) {
+            Some(k) => k,
+            None => return false,
+        };
+
+        self.moves.insert(key, symmetry.apply_dir(dir));
+        return true;
+    }
+}
+
+impl Battlesnake {
+    // the first few turns of standard games repeat, so their moves were
+    // worked out ahead of time. false when this position isn't in the book
+    // or its move has since been ruled out
+    pub fn use_opening_book(
+        self: &Self,
+        sm: &mut ScoredMoves,
+        b: &Board,
+        book: &OpeningBook,
+        turn: u16,
+        ruleset: &str,
+    ) -> bool {
+        let dir = match book.lookup(b, self, turn, ruleset) {
+            Some(d) => d,
+            None => return false,
+        };
+
+        if sm.is_dead(dir) {
+            return false;
+        }
+
+        sm.get_mut(dir).score += BOOK_WEIGHT;
+        println!("[book]: going {}", dir.get_str());
+
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    // no two symmetries map this position onto itself
+    fn position() -> Fixture {
+        return Fixture::new(
+            11,
+            11,
+            vec![
+                snake("me", &[(1, 2), (1, 1), (1, 1)], 99),
+                snake("a", &[(9, 5), (9, 4), (9, 4)], 99),
+                snake("b", &[(5, 9), (5, 9), (5, 9)], 100),
+            ],
+        )
+        .with_food(&[(0, 3), (8, 8)]);
+    }
+
+    // the board and us seen through `s`
+    fn transformed(s: &Symmetry, b: &Board, you: &Battlesnake) -> (Board, Battlesnake) {
+        let turn = |snake: &Battlesnake| -> Battlesnake {
+            let mut t = snake.clone();
+            t.body = snake.body.iter().map(|c| s.apply(c, b.width)).collect();
+            t.head = s.apply(&snake.head, b.width);
+            return t;
+        };
+
+        let mut board = b.clone();
+        board.snakes = b.snakes.iter().map(turn).collect();
+        board.food = b.food.iter().map(|c| s.apply(c, b.width)).collect();
+
+        return (board, turn(you));
+    }
+
+    #[test]
+    fn every_symmetry_gives_the_same_key() {
+        let f = position();
+        let (b, you) = (f.board(), f.you());
+        let (key, _) = normalise(&b, &you, 2, "standard").unwrap();
+
+        for s in &SYMMETRIES {
+            let (board, me) = transformed(s, &b, &you);
+            let (k, _) = normalise(&board, &me, 2, "standard").unwrap();
+            assert_eq!(k, key, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn the_ruleset_is_part_of_the_key() {
+        let f = position();
+        let (b, you) = (f.board(), f.you());
+
+        let mut book = OpeningBook::default();
+        assert!(book.insert(&b, &you, 2, "standard", Direction::Up));
+
+        assert_eq!(book.lookup(&b, &you, 2, "standard"), Some(Direction::Up));
+        assert_eq!(book.lookup(&b, &you, 2, "constrictor"), None);
+        assert!(!book.covers(&b, &you, 2, "squad"));
+    }
+
+    #[test]
+    fn moves_round_trip_through_every_symmetry() {
+        let f = position();
+        let (b, you) = (f.board(), f.you());
+
+        for dir in Direction::ALL {
+            let mut book = OpeningBook::default();
+            assert!(book.insert(&b, &you, 2, "standard", dir));
+
+            for s in &SYMMETRIES {
+                let (board, me) = transformed(s, &b, &you);
+                assert_eq!(
+                    book.lookup(&board, &me, 2, "standard"),
+                    Some(s.apply_dir(dir)),
+                    "{:?} {:?}",
+                    s,
+                    dir
+                );
+                assert_eq!(s.invert_dir(s.apply_dir(dir)), dir);
+            }
+        }
+    }
+
+    #[test]
+    fn deltas_map_back_to_their_direction() {
+        for dir in Direction::ALL {
+            assert_eq!(from_delta(to_delta(dir)), dir);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not a single step")]
+    fn a_diagonal_is_a_bug() {
+        from_delta((1, 1));
+    }
+}
@@ -0,0 +1,299 @@
+use super::distance::vacate_times;
+use super::grid::Grid;
+use super::{Battlesnake, Board, Coord, Direction, ScoredMoves};
+
+const ARTICULATION_PENALTY: i16 = -20;
+const CORRIDOR_PENALTY: i16 = -40;
+const SEALABLE_PENALTY: i16 = -200;
+
+// the graph of cells that are free next turn, with the cells whose removal
+// would cut it into pieces
+pub struct FreeSpace {
+    grid: Grid,
+    free: Vec<bool>,
+    articulation: Vec<bool>,
+}
+
+impl FreeSpace {
+    pub fn new(b: &Board) -> FreeSpace {
+        let grid = Grid::new(b);
+
+        // a tail that moves this turn is free, anything longer lived is not
+        let free: Vec<bool> = vacate_times(b, &grid).iter().map(|t| *t <= 1).collect();
+        let articulation = find_articulation_points(&grid, &free);
+
+        return FreeSpace {
+            grid,
+            free,
+            articulation,
+        };
+    }
+
+    pub fn is_free(self: &Self, c: &Coord) -> bool {
+        return match self.grid.index(c) {
+            Some(i) => self.free[i],
+            None => false,
+        };
+    }
+
+    // occupying this cell would split the free space it belongs to
+    pub fn is_articulation_point(self: &Self, c: &Coord) -> bool {
+        return match self.grid.index(c) {
+            Some(i) => self.articulation[i],
+            None => false,
+        };
+    }
+
+    // the free cells we could carry on to after stepping onto `c`
+    pub fn get_exits(self: &Self, c: &Coord) -> Vec<Coord> {
+        let i = match self.grid.index(c) {
+            Some(i) => i,
+            None => return Vec::new(),
+        };
+
+        let mut nbs = [0usize; 4];
+        let n = self.grid.neighbours(i, &mut nbs);
+
+        return nbs[..n]
+            .iter()
+            .filter(|j| self.free[**j])
+            .map(|j| self.grid.coord(*j))
+            .collect();
+    }
+}
+
+// tarjan's low-link over the free cells. iterative so big boards can't
+// blow the stack of the handler thread
+fn find_articulation_points(grid: &Grid, free: &[bool]) -> Vec<bool> {
+    let cells = grid.cells();
+    let mut disc = vec![0u32; cells];
+    let mut low = vec![0u32; cells];
+    let mut parent = vec![usize::MAX; cells];
+    let mut articulation = vec![false; cells];
+    let mut time: u32 = 0;
+    let mut nbs = [0usize; 4];
+
+    for root in 0..cells {
+        if !free[root] || disc[root] != 0 {
+            continue;
+        }
+
+        time += 1;
+        disc[root] = time;
+        low[root] = time;
+
+        let mut root_children = 0;
+        // each entry is a cell and how many of its neighbours we've tried
+        let mut stack: Vec<(usize, usize)> = vec![(root, 0)];
+
+        while let Some(&(u, tried)) = stack.last() {
+            let n = grid.neighbours(u, &mut nbs);
+
+            if tried < n {
+                if let Some(top) = stack.last_mut() {
+                    top.1 += 1;
+                }
+
+                let v = nbs[tried];
+                if !free[v] {
+                    continue;
+                }
+
+                if disc[v] == 0 {
+                    parent[v] = u;
+                    time += 1;
+                    disc[v] = time;
+                    low[v] = time;
+
+                    if u == root {
+                        root_children += 1;
+                    }
+
+                    stack.push((v, 0));
+                } else if v != parent[u] {
+                    low[u] = low[u].min(disc[v]);
+                }
+            } else {
+                stack.pop();
+
+                if let Some(&(p, _)) = stack.last() {
+                    low[p] = low[p].min(low[u]);
+
+                    if p != root && low[u] >= disc[p] {
+                        articulation[p] = true;
+                    }
+                }
+            }
+        }
+
+        if root_children > 1 {
+            articulation[root] = true;
+        }
+    }
+
+    return articulation;
+}
+
+impl Battlesnake {
+    // penalise moves into chokepoints: cells that split the space we have,
+    // and one-wide tunnels. a tunnel whose only way out is next to an
+    // opponent head can be closed behind us in a single move
+    pub fn use_chokepoints(self: &Self, sm: &mut ScoredMoves, b: &Board) {
+        let space = FreeSpace::new(b);
+
+        for dir in Direction::ALL {
+            if sm.is_dead(dir) {
+                continue;
+            }
+
+            let next = match self.head.get_next_coord(dir, b) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            if !space.is_free(&next) {
+                continue;
+            }
+
+            let exits = space.get_exits(&next);
+            let mut penalty: i16 = 0;
+
+            if exits.len() <= 1 {
+                penalty += CORRIDOR_PENALTY;
+
+                let sealable = exits.iter().any(|e| {
+                    b.snakes
+                        .iter()
+                        .any(|s| s.id != self.id && s.head.get_distance_on(e, b) <= 1)
+                });
+
+                if sealable || exits.is_empty() {
+                    penalty += SEALABLE_PENALTY;
+                }
+            } else if space.is_articulation_point(&next) {
+                penalty += ARTICULATION_PENALTY;
+            }
+
+            if penalty != 0 {
+                sm.get_mut(dir).score += penalty;
+                println!(
+                    "[choke {}]: {} ({} exits)",
+                    dir.get_str(),
+                    penalty,
+                    exits.len()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    fn grid(width: u16, height: u16) -> Grid {
+        return Grid::new(&Fixture::new(width, height, vec![snake("me", &[(0, 0)], 90)]).board());
+    }
+
+    fn components(grid: &Grid, free: &[bool]) -> usize {
+        let mut seen = vec![false; free.len()];
+        let mut nbs = [0usize; 4];
+        let mut count = 0;
+
+        for start in 0..free.len() {
+            if !free[start] || seen[start] {
+                continue;
+            }
+
+            count += 1;
+            seen[start] = true;
+            let mut stack = vec![start];
+            while let Some(u) = stack.pop() {
+                let n = grid.neighbours(u, &mut nbs);
+                for &v in &nbs[..n] {
+                    if free[v] && !seen[v] {
+                        seen[v] = true;
+                        stack.push(v);
+                    }
+                }
+            }
+        }
+
+        return count;
+    }
+
+    #[test]
+    fn articulation_points_split_the_space() {
+        // every free cell removed in turn, on boards with scattered walls
+        let mut seed: u32 = 12345;
+        for (w, h) in [(1, 6), (4, 4), (6, 5), (7, 7)] {
+            let g = grid(w, h);
+            for _ in 0..20 {
+                let free: Vec<bool> = (0..g.cells())
+                    .map(|_| {
+                        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                        (seed >> 16) % 10 < 7
+                    })
+                    .collect();
+
+                let found = find_articulation_points(&g, &free);
+                let before = components(&g, &free);
+
+                for cell in 0..g.cells() {
+                    let mut without = free.clone();
+                    without[cell] = false;
+                    let splits = free[cell] && components(&g, &without) > before;
+                    assert_eq!(found[cell], splits, "cell {} of {:?}", cell, free);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_dead_end_is_penalised() {
+        // the cell below us is boxed in by two heads
+        let f = Fixture::new(
+            5,
+            4,
+            vec![
+                snake("me", &[(2, 1), (3, 1), (4, 1)], 90),
+                snake("b", &[(1, 0), (0, 0), (0, 1)], 90),
+                snake("c", &[(3, 0), (4, 0)], 90),
+            ],
+        );
+        let b = f.board();
+
+        let mut sm = ScoredMoves::init();
+        f.you().use_chokepoints(&mut sm, &b);
+
+        assert_eq!(sm.get(Direction::Up).score, 0);
+        assert_eq!(
+            sm.get(Direction::Down).score,
+            CORRIDOR_PENALTY + SEALABLE_PENALTY
+        );
+    }
+
+    #[test]
+    fn a_tunnel_next_to_an_opponent_can_be_sealed() {
+        // the way out of the cell on our left is one move from b's head
+        let f = Fixture::new(
+            5,
+            3,
+            vec![
+                snake("me", &[(2, 1), (3, 1), (4, 1)], 90),
+                snake("b", &[(0, 2), (1, 2), (2, 2), (3, 2)], 90),
+                snake("c", &[(1, 0), (2, 0), (3, 0), (4, 0)], 90),
+            ],
+        );
+        let b = f.board();
+
+        let mut sm = ScoredMoves::init();
+        f.you().use_chokepoints(&mut sm, &b);
+
+        assert_eq!(
+            sm.get(Direction::Left).score,
+            CORRIDOR_PENALTY + SEALABLE_PENALTY
+        );
+    }
+}
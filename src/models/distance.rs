@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+
+use super::grid::Grid;
+use super::{Board, Coord};
+
+pub const UNREACHABLE: u16 = u16::MAX;
+
+// number of moves before each cell stops being covered by a body. a segment
+// i cells from the head of a snake of length L is gone after L - i moves, a
+// stacked tail keeps its cell for one more turn. empty cells are free at 0
+pub fn vacate_times(b: &Board, grid: &Grid) -> Vec<u16> {
+    let mut free_at = vec![0u16; grid.cells()];
+
+    for snake in &b.snakes {
+        let len = snake.body.len();
+        for (i, c) in snake.body.iter().enumerate() {
+            if let Some(idx) = grid.index(c) {
+                let t = (len - i) as u16;
+                if t > free_at[idx] {
+                    free_at[idx] = t;
+                }
+            }
+        }
+    }
+
+    return free_at;
+}
+
+// shortest number of moves from every snake head to every cell, where a
+// body segment only blocks a snake that would get there before it moves away
+pub struct DistanceMap {
+    grid: Grid,
+    ids: Vec<String>,
+    dist: Vec<Vec<u16>>,
+}
+
+impl DistanceMap {
+    pub fn new(b: &Board) -> DistanceMap {
+        let grid = Grid::new(b);
+        let cells = grid.cells();
+        let free_at = vacate_times(b, &grid);
+
+        let ids: Vec<String> = b.snakes.iter().map(|s| s.id.clone()).collect();
+        let mut dist = vec![vec![UNREACHABLE; cells]; b.snakes.len()];
+
+        // every head goes in the same queue, so the search runs one layer
+        // of moves at a time for all snakes together
+        let mut q: VecDeque<(usize, usize)> = VecDeque::new();
+        for (s, snake) in b.snakes.iter().enumerate() {
+            if let Some(h) = grid.index(&snake.head) {
+                dist[s][h] = 0;
+                q.push_back((s, h));
+            }
+        }
+
+        let mut nbs = [0usize; 4];
+
+        while let Some((s, cell)) = q.pop_front() {
+            let next_d = dist[s][cell] + 1;
+
+            let n = grid.neighbours(cell, &mut nbs);
+            for &next in &nbs[..n] {
+                if dist[s][next] != UNREACHABLE || free_at[next] > next_d {
+                    continue;
+                }
+
+                dist[s][next] = next_d;
+                q.push_back((s, next));
+            }
+        }
+
+        return DistanceMap { grid, ids, dist };
+    }
+
+    pub fn snake_count(self: &Self) -> usize {
+        return self.ids.len();
+    }
+
+    pub fn get_grid(self: &Self) -> &Grid {
+        return &self.grid;
+    }
+
+    // distance by flat cell index, see Grid
+    pub fn get_at(self: &Self, snake: usize, cell: usize) -> u16 {
+        return self.dist[snake][cell];
+    }
+
+    pub fn index_of(self: &Self, id: &str) -> Option<usize> {
+        return self.ids.iter().position(|i| i == id);
+    }
+
+    pub fn get(self: &Self, snake: usize, c: &Coord) -> u16 {
+        return match self.grid.index(c) {
+            Some(i) => self.get_at(snake, i),
+            None => UNREACHABLE,
+        };
+    }
+
+    pub fn distance(self: &Self, id: &str, c: &Coord) -> Option<u16> {
+        let d = self.get(self.index_of(id)?, c);
+        if d == UNREACHABLE {
+            return None;
+        }
+
+        return Some(d);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    fn at(x: u16, y: u16) -> Coord {
+        return Coord { x, y };
+    }
+
+    #[test]
+    fn bodies_leave_from_the_tail() {
+        let f = Fixture::new(
+            5,
+            2,
+            vec![
+                snake("a", &[(2, 0), (1, 0), (0, 0)], 90),
+                snake("b", &[(2, 1), (1, 1), (1, 1)], 90),
+            ],
+        );
+        let b = f.board();
+        let grid = Grid::new(&b);
+        let free_at = vacate_times(&b, &grid);
+        let time = |x, y| free_at[grid.index(&at(x, y)).unwrap()];
+
+        assert_eq!((time(2, 0), time(1, 0), time(0, 0)), (3, 2, 1));
+        // the stacked tail is still there after the first move
+        assert_eq!((time(2, 1), time(1, 1)), (3, 2));
+        assert_eq!(time(4, 0), 0);
+    }
+
+    #[test]
+    fn a_body_only_blocks_whoever_gets_there_first() {
+        // b's tail is gone by the time we reach it, its head isn't
+        let f = Fixture::new(
+            7,
+            1,
+            vec![
+                snake("me", &[(0, 0)], 90),
+                snake("b", &[(4, 0), (3, 0), (2, 0)], 90),
+            ],
+        );
+        let dm = DistanceMap::new(&f.board());
+
+        assert_eq!(dm.distance("me", &at(0, 0)), Some(0));
+        assert_eq!(dm.distance("me", &at(2, 0)), Some(2));
+        assert_eq!(dm.distance("me", &at(4, 0)), Some(4));
+        assert_eq!(dm.distance("me", &at(6, 0)), Some(6));
+
+        let g = Fixture::new(
+            5,
+            1,
+            vec![
+                snake("me", &[(0, 0)], 90),
+                snake("b", &[(2, 0), (3, 0), (4, 0)], 90),
+            ],
+        );
+        let dm = DistanceMap::new(&g.board());
+
+        assert_eq!(dm.distance("me", &at(1, 0)), Some(1));
+        assert_eq!(dm.distance("me", &at(2, 0)), None);
+        assert_eq!(dm.distance("b", &at(1, 0)), Some(1));
+        assert_eq!(dm.distance("nobody", &at(1, 0)), None);
+    }
+
+    #[test]
+    fn every_snake_gets_its_own_distances() {
+        let f = Fixture::new(
+            5,
+            5,
+            vec![
+                snake("me", &[(0, 0), (0, 0)], 90),
+                snake("b", &[(4, 4), (4, 4)], 90),
+            ],
+        );
+        let b = f.board();
+        let dm = DistanceMap::new(&b);
+
+        assert_eq!(dm.snake_count(), 2);
+        assert_eq!(dm.distance("me", &at(4, 3)), Some(7));
+        assert_eq!(dm.distance("b", &at(0, 1)), Some(7));
+        assert_eq!(dm.get(0, &at(5, 0)), UNREACHABLE);
+    }
+}
@@ -0,0 +1,519 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use super::parallel;
+use super::state::GameState;
+use super::transposition::{Bound, TranspositionTable};
+use super::zobrist;
+use super::{Battlesnake, Board, Direction, MoveRequest, ScoredMoves};
+
+const WIN: i16 = 10_000;
+// scores this close to WIN are forced results, not evaluations
+const PROVEN: i16 = WIN - 1_000;
+const MAX_DEPTH: u8 = 40;
+// enough to override anything the heuristics still add
+const DUEL_WEIGHT: i16 = 5_000;
+
+// whatever the engine spends getting the request to us and the answer back
+const LATENCY_ALLOWANCE_MS: u16 = 150;
+const MIN_BUDGET_MS: u16 = 10;
+
+const LENGTH_WEIGHT: i16 = 10;
+const TERRITORY_WEIGHT: i16 = 2;
+const LOW_HEALTH: u8 = 15;
+
+// health is only hashed point by point below EXACT_HEALTH, a search must
+// not be able to see it matter any higher
+const _: () = assert!(MAX_DEPTH + LOW_HEALTH <= zobrist::EXACT_HEALTH);
+
+// 2^17 positions, one table per game kept between turns since the next
+// search starts from a position this one has already seen
+const TABLE_BITS: u32 = 17;
+
+pub fn new_duel_table() -> TranspositionTable {
+    return TranspositionTable::new(TABLE_BITS);
+}
+
+// forced results are stored counted from the position rather than from the
+// root, so they stay right when the position comes up again at another ply
+fn to_table(score: i16, ply: u8) -> i16 {
+    if score >= PROVEN {
+        return score + ply as i16;
+    }
+    if score <= -PROVEN {
+        return score - ply as i16;
+    }
+    return score;
+}
+
+fn from_table(score: i16, ply: u8) -> i16 {
+    if score >= PROVEN {
+        return score - ply as i16;
+    }
+    if score <= -PROVEN {
+        return score + ply as i16;
+    }
+    return score;
+}
+
+impl MoveRequest {
+    // half of what's left once the network has had its share, so the
+    // heuristics that run before the search still fit
+    pub fn get_search_budget(self: &Self) -> Duration {
+        let ms = self
+            .get_timeout()
+            .saturating_sub(LATENCY_ALLOWANCE_MS)
+            .max(MIN_BUDGET_MS * 2)
+            / 2;
+        return Duration::from_millis(ms as u64);
+    }
+}
+
+// alpha-beta over the two snakes. moves are simultaneous, so the opponent
+// is made to pick after seeing ours: every line is as bad for us as it
+// could be, and a move that still wins here wins for real
+struct DuelSearch<'a> {
+    state: GameState,
+    table: &'a TranspositionTable,
+    // scores are from our side, positions searched from the other snake's
+    // side mustn't share them
+    perspective: u64,
+    me: usize,
+    them: usize,
+    deadline: Instant,
+    // set by whichever worker finishes first, the rest stop with it
+    done: &'a AtomicBool,
+    nodes: u64,
+    stopped: bool,
+}
+
+impl<'a> DuelSearch<'a> {
+    fn new(
+        state: &GameState,
+        table: &'a TranspositionTable,
+        me: usize,
+        deadline: Instant,
+        done: &'a AtomicBool,
+    ) -> DuelSearch<'a> {
+        return DuelSearch {
+            state: state.clone(),
+            table,
+            perspective: zobrist::perspective_key(me),
+            me,
+            them: 1 - me,
+            deadline,
+            done,
+            nodes: 0,
+            stopped: false,
+        };
+    }
+
+    fn out_of_time(self: &mut Self) -> bool {
+        if !self.stopped
+            && self.nodes.is_multiple_of(1024)
+            && (Instant::now() >= self.deadline || self.done.load(Ordering::Relaxed))
+        {
+            self.stopped = true;
+        }
+        return self.stopped;
+    }
+
+    // from our side. a win sooner or a loss later is worth more, so the
+    // search takes the quickest kill and holds out longest when lost
+    fn evaluate(self: &mut Self, ply: u8) -> i16 {
+        let us = self.state.is_alive(self.me);
+        let them = self.state.is_alive(self.them);
+
+        match (us, them) {
+            (false, false) => return 0,
+            (false, true) => return -WIN + ply as i16,
+            (true, false) => return WIN - ply as i16,
+            _ => {}
+        }
+
+        let (ours, theirs) = self.state.count_territory(self.me, self.them);
+        let length =
+            self.state.get_length(self.me) as i16 - self.state.get_length(self.them) as i16;
+
+        let mut score = (ours as i16 - theirs as i16) * TERRITORY_WEIGHT
+            + length.clamp(-10, 10) * LENGTH_WEIGHT;
+
+        if self.state.get_health(self.me) < LOW_HEALTH {
+            score -= (LOW_HEALTH - self.state.get_health(self.me)) as i16 * LENGTH_WEIGHT;
+        }
+
+        return score;
+    }
+
+    fn is_over(self: &Self) -> bool {
+        return !self.state.is_alive(self.me) || !self.state.is_alive(self.them);
+    }
+
+    // our move, then theirs against it
+    fn search(self: &mut Self, depth: u8, mut alpha: i16, beta: i16, ply: u8) -> i16 {
+        self.nodes += 1;
+
+        if depth == 0 || self.is_over() {
+            return self.evaluate(ply);
+        }
+
+        if self.out_of_time() {
+            return 0;
+        }
+
+        let key = self.state.get_hash() ^ self.perspective;
+        let stored = self.table.get(key);
+
+        if let Some(e) = stored
+            && e.get_depth() >= depth
+        {
+            let s = from_table(e.get_score(), ply);
+            match e.get_bound() {
+                Bound::Exact => return s,
+                Bound::Lower if s >= beta => return s,
+                Bound::Upper if s <= alpha => return s,
+                _ => {}
+            }
+        }
+
+        let mut ours = [Direction::Up; 4];
+        let mut n = self.state.legal_moves(self.me, &mut ours);
+        if n == 0 {
+            ours[0] = Direction::Up;
+            n = 1;
+        }
+
+        // the best move last time we were here goes first
+        if let Some(best) = stored.and_then(|e| e.get_best())
+            && let Some(i) = ours[..n].iter().position(|d| *d == best)
+        {
+            ours.swap(0, i);
+        }
+
+        let start_alpha = alpha;
+        let mut best_score = -WIN - 1;
+        let mut best_move = ours[0];
+
+        for &dir in &ours[..n] {
+            let score = self.reply(dir, depth, alpha, beta, ply);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = dir;
+            }
+
+            if score > alpha {
+                alpha = score;
+            }
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_score <= start_alpha {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+
+        self.table.store(
+            key,
+            depth,
+            to_table(best_score, ply),
+            bound,
+            Some(best_move),
+        );
+        return best_score;
+    }
+
+    // the opponent's best answer to our `dir`
+    fn reply(
+        self: &mut Self,
+        dir: Direction,
+        depth: u8,
+        alpha: i16,
+        mut beta: i16,
+        ply: u8,
+    ) -> i16 {
+        let mut theirs = [Direction::Up; 4];
+        let mut n = self.state.legal_moves(self.them, &mut theirs);
+        if n == 0 {
+            theirs[0] = Direction::Up;
+            n = 1;
+        }
+
+        let mut moves = [Direction::Up; 2];
+        moves[self.me] = dir;
+
+        let mut worst = WIN + 1;
+
+        for &reply in &theirs[..n] {
+            moves[self.them] = reply;
+
+            self.state.make_moves(&moves);
+            let score = self.search(depth - 1, alpha, beta, ply + 1);
+            self.state.unmake();
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score < worst {
+                worst = score;
+            }
+
+            if worst < beta {
+                beta = worst;
+            }
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        return worst;
+    }
+
+    // scores for each of our legal moves from the last depth that finished,
+    // and that depth. worker 0 is the main search. the others start on a
+    // different move, and every other one a depth ahead, so between them
+    // they fill the shared table with what the main search wants next
+    fn iterate(self: &mut Self, worker: usize, max_depth: u8) -> (u8, Vec<(Direction, i16)>) {
+        let mut ours = [Direction::Up; 4];
+        let n = self.state.legal_moves(self.me, &mut ours);
+        let mut results: Vec<(Direction, i16)> = Vec::new();
+        let mut completed = 0;
+
+        if n > 0 {
+            ours[..n].rotate_left(worker % n);
+        }
+
+        for depth in (1 + (worker % 2) as u8)..=max_depth {
+            let mut scores: Vec<(Direction, i16)> = Vec::with_capacity(n);
+
+            for &dir in &ours[..n] {
+                let score = self.reply(dir, depth, -WIN - 1, WIN + 1, 0);
+                if self.stopped {
+                    break;
+                }
+                scores.push((dir, score));
+            }
+
+            if self.stopped {
+                break;
+            }
+
+            // the next depth looks at the best move first
+            scores.sort_by_key(|(_, s)| std::cmp::Reverse(*s));
+            for (i, (dir, _)) in scores.iter().enumerate() {
+                ours[i] = *dir;
+            }
+
+            results = scores;
+            completed = depth;
+            if worker == 0 {
+                println!(
+                    "[duel depth {}]: {:?} ({} nodes)",
+                    depth,
+                    results.first(),
+                    self.nodes
+                );
+            }
+
+            // nothing left to learn once every move's outcome is forced
+            if results.iter().all(|(_, s)| s.abs() >= PROVEN) {
+                break;
+            }
+        }
+
+        self.done.store(true, Ordering::Relaxed);
+        return (completed, results);
+    }
+}
+
+impl Battlesnake {
+    pub fn is_in_duel(self: &Self, b: &Board) -> bool {
+        return b.snakes.len() == 2 && b.snakes.iter().any(|s| s.id == self.id);
+    }
+
+    // one opponent left: search the game itself instead of guessing with
+    // heuristics, and back the best move far enough that nothing else
+    // changes it. every core searches into the same table and the deepest
+    // finished answer is played. false when the search didn't get through
+    // a single depth. `state` is `b`'s, `table` is kept for the rest of the game
+    pub fn search_duel(
+        self: &Self,
+        sm: &mut ScoredMoves,
+        b: &Board,
+        state: &GameState,
+        table: &TranspositionTable,
+        budget: Duration,
+    ) -> bool {
+        let me = match b.snakes.iter().position(|s| s.id == self.id) {
+            Some(i) => i,
+            None => return false,
+        };
+
+        if !state.is_alive(me) || !state.is_alive(1 - me) {
+            return false;
+        }
+
+        let deadline = Instant::now() + budget;
+        let done = AtomicBool::new(false);
+        let workers = parallel::worker_count();
+
+        let finished = parallel::run_workers(workers, |worker| {
+            let mut search = DuelSearch::new(state, table, me, deadline, &done);
+            let (depth, results) = search.iterate(worker, MAX_DEPTH);
+            (depth, search.nodes, results)
+        });
+
+        let nodes: u64 = finished.iter().map(|f| f.1).sum();
+        // the deepest answer, the main search's when it's as deep as any
+        let (depth, _, results) = match finished.into_iter().rev().max_by_key(|f| f.0) {
+            Some(f) => f,
+            None => return false,
+        };
+        println!(
+            "[duel]: {} workers, depth {}, {} nodes",
+            workers, depth, nodes
+        );
+
+        let best = match results.first() {
+            Some(r) => *r,
+            None => return false,
+        };
+
+        if sm.is_dead(best.0) && best.1 > -PROVEN {
+            // the heuristics know something the search doesn't, let them decide
+            return false;
+        }
+
+        sm.get_mut(best.0).score += DUEL_WEIGHT;
+        println!("[duel]: going {} scored {}", best.0.get_str(), best.1);
+
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    fn search(
+        f: &Fixture,
+        me: usize,
+        table: &TranspositionTable,
+        depth: u8,
+    ) -> Vec<(Direction, i16)> {
+        let state = GameState::new(&f.board());
+        let done = AtomicBool::new(false);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        return DuelSearch::new(&state, table, me, deadline, &done)
+            .iterate(0, depth)
+            .1;
+    }
+
+    // they're shorter and cornered: going left either meets their head now
+    // or takes the only square they have left next turn
+    fn win_in_two(health: u16) -> Fixture {
+        return Fixture::new(
+            5,
+            5,
+            vec![
+                snake(
+                    "me",
+                    &[(2, 1), (2, 2), (2, 3), (2, 4), (1, 4), (0, 4), (0, 3)],
+                    health,
+                ),
+                snake("b", &[(0, 1), (0, 2), (1, 2)], health),
+            ],
+        );
+    }
+
+    #[test]
+    fn finds_a_forced_win_in_two() {
+        let results = search(&win_in_two(90), 0, &new_duel_table(), 6);
+        assert_eq!(results[0], (Direction::Left, WIN - 2));
+    }
+
+    #[test]
+    fn workers_sharing_a_table_agree() {
+        let f = win_in_two(90);
+        let state = GameState::new(&f.board());
+        let table = new_duel_table();
+        let done = AtomicBool::new(false);
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        let finished = parallel::run_workers(3, |worker| {
+            DuelSearch::new(&state, &table, 0, deadline, &done).iterate(worker, 6)
+        });
+
+        let deepest = finished.iter().max_by_key(|f| f.0).unwrap();
+        assert_eq!(deepest.1[0], (Direction::Left, WIN - 2));
+    }
+
+    #[test]
+    fn sees_a_forced_head_to_head_loss() {
+        // a corridor one square high with a longer snake coming the other way
+        let f = Fixture::new(
+            7,
+            1,
+            vec![
+                snake("me", &[(2, 0), (1, 0), (0, 0)], 90),
+                snake("b", &[(4, 0), (5, 0), (6, 0), (6, 0)], 90),
+            ],
+        );
+
+        let results = search(&f, 0, &new_duel_table(), 6);
+        assert_eq!(results, vec![(Direction::Right, -WIN + 1)]);
+    }
+
+    #[test]
+    fn mate_scores_are_stored_from_the_position() {
+        for ply in [0, 1, 7, MAX_DEPTH] {
+            for score in [WIN - 3, -WIN + 5, 0, 120, -PROVEN] {
+                assert_eq!(from_table(to_table(score, ply), ply), score);
+            }
+        }
+
+        // a table filled from the next position has its wins one ply
+        // nearer the root than they are here. they have to step down into
+        // it, and we can too
+        let before = Fixture::new(
+            5,
+            5,
+            vec![
+                snake(
+                    "me",
+                    &[(2, 2), (2, 3), (2, 4), (1, 4), (0, 4), (0, 3), (0, 3)],
+                    90,
+                ),
+                snake("b", &[(0, 2), (1, 2), (1, 3)], 90),
+            ],
+        );
+        let fresh = search(&before, 0, &new_duel_table(), 6);
+
+        let table = new_duel_table();
+        search(&win_in_two(89), 0, &table, 6);
+        assert_eq!(search(&before, 0, &table, 6)[0], fresh[0]);
+    }
+
+    #[test]
+    fn each_side_keeps_its_own_scores() {
+        let table = new_duel_table();
+        let f = win_in_two(90);
+
+        search(&f, 0, &table, 6);
+        let theirs = search(&f, 1, &table, 6);
+        assert!(theirs.iter().all(|(_, s)| *s <= -PROVEN), "{:?}", theirs);
+    }
+}
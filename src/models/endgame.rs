@@ -0,0 +1,360 @@
+use std::time::Duration;
+
+use super::distance::UNREACHABLE;
+use super::survival::{MAX_DEPTH, Survival};
+use super::{Battlesnake, Board, Direction, ScoredMoves, flood_fill_arrivals};
+
+// each snake solved gets this long, we're usually one of very few
+const ENDGAME_BUDGET: Duration = Duration::from_millis(60);
+const ENDGAME_WEIGHT: i16 = 3000;
+// kept above certain death, the race is lost only if nothing else kills them
+const LOSING_PENALTY: i16 = 500;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outlast {
+    Win,
+    Loss,
+    Draw,
+    Unknown,
+}
+
+// who runs out first, given how long we last and how long the opponent who
+// lasts longest does, None if we can't tell. each is (turns, exact) and
+// inexact results are only lower bounds
+fn compare_survival(ours: (u16, bool), theirs: Option<(u16, bool)>) -> Outlast {
+    let (mine, mine_exact) = ours;
+    let (turns, exact) = theirs.unwrap_or((MAX_DEPTH, false));
+
+    if mine >= MAX_DEPTH && turns >= MAX_DEPTH {
+        return Outlast::Unknown;
+    }
+
+    if mine_exact && turns > mine {
+        return Outlast::Loss;
+    }
+
+    if exact && mine > turns {
+        return Outlast::Win;
+    }
+
+    if exact && mine_exact && mine == turns {
+        return Outlast::Draw;
+    }
+
+    return Outlast::Unknown;
+}
+
+// whether a snake with arrival times `other` can get into the region
+// `region` covers before its owner runs out of ways to stall, about one lap
+// of the region. a wall that takes longer than that to move seals it
+fn breaks_into(region: &[u16], other: &[u16]) -> bool {
+    let lap = region.iter().filter(|a| **a != UNREACHABLE).count();
+
+    return region
+        .iter()
+        .zip(other)
+        .any(|(a, b)| *a != UNREACHABLE && (*b as usize) <= lap);
+}
+
+impl Battlesnake {
+    // how soon this snake can stand on each cell, waiting for bodies to
+    // move out of the way if it has to
+    pub fn get_reach(self: &Self, b: &Board) -> Vec<u16> {
+        let mut reach: Vec<u16> = Vec::new();
+
+        for n in self.head.get_neighbours(b) {
+            let arrivals = flood_fill_arrivals(b, n);
+            if reach.is_empty() {
+                reach = arrivals;
+                continue;
+            }
+
+            for (r, a) in reach.iter_mut().zip(&arrivals) {
+                *r = (*r).min(*a);
+            }
+        }
+
+        return reach;
+    }
+
+    // whether going `dir` leaves us somewhere no opponent can get into
+    fn is_sealed_after(self: &Self, dir: Direction, b: &Board) -> bool {
+        let next = match self.head.get_next_coord(dir, b) {
+            Some(c) => c,
+            None => return false,
+        };
+
+        let moved = b.with_snake_moved(&self.id, next);
+        let me = match moved.snakes.iter().find(|s| s.id == self.id) {
+            Some(s) => s,
+            None => return false,
+        };
+
+        let ours = me.get_reach(&moved);
+
+        return !moved
+            .snakes
+            .iter()
+            .filter(|s| s.id != self.id)
+            .any(|s| breaks_into(&ours, &s.get_reach(&moved)));
+    }
+
+    // how long the opponent who lasts longest does, None while opponents
+    // still share space with each other and can keep playing
+    fn get_opponent_survival(self: &Self, b: &Board) -> Option<(u16, bool)> {
+        let opponents: Vec<(&Battlesnake, Vec<u16>)> = b
+            .snakes
+            .iter()
+            .filter(|s| s.id != self.id)
+            .map(|s| (s, s.get_reach(b)))
+            .collect();
+
+        let shared = opponents.iter().enumerate().any(|(i, (_, a))| {
+            opponents
+                .iter()
+                .enumerate()
+                .any(|(j, (_, b))| i != j && breaks_into(a, b))
+        });
+
+        if shared {
+            return None;
+        }
+
+        let mut longest = (0, true);
+        for (opponent, _) in &opponents {
+            // no move at all means they die next turn
+            if let Some(s) = opponent.find_longest_survival(b, &Direction::ALL, ENDGAME_BUDGET) {
+                longest.1 &= s.is_exact();
+                longest.0 = longest.0.max(s.get_turns());
+            }
+        }
+
+        return Some(longest);
+    }
+
+    // once a move cuts us off from every opponent, nothing we do affects
+    // them or them us, so the game is decided by who runs out of room or
+    // food first. moves into a region we outlast them in are worth taking,
+    // moves into one we don't are worth avoiding. when every move seals us
+    // in, play the one that stalls longest and stop there. false when we
+    // can still get out
+    pub fn solve_endgame(self: &Self, sm: &mut ScoredMoves, b: &Board) -> bool {
+        if b.snakes.len() < 2 {
+            return false;
+        }
+
+        let alive = sm.get_alive_moves();
+        let sealed: Vec<Direction> = alive
+            .iter()
+            .copied()
+            .filter(|dir| self.is_sealed_after(*dir, b))
+            .collect();
+
+        if sealed.is_empty() {
+            return false;
+        }
+
+        let theirs = self.get_opponent_survival(b);
+        let mut longest: Option<Survival> = None;
+
+        for dir in sealed.iter() {
+            let mine = match self.find_longest_survival(b, &[*dir], ENDGAME_BUDGET) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let outcome = compare_survival((mine.get_turns(), mine.is_exact()), theirs);
+            match outcome {
+                Outlast::Win => sm.get_mut(*dir).score += ENDGAME_WEIGHT,
+                Outlast::Loss => sm.get_mut(*dir).score -= LOSING_PENALTY,
+                Outlast::Draw | Outlast::Unknown => (),
+            }
+
+            println!(
+                "[endgame]: going {} seals us in for {} turns{} ({:?})",
+                dir.get_str(),
+                mine.get_turns(),
+                if mine.is_exact() { "" } else { " or more" },
+                outcome
+            );
+
+            if longest.is_none_or(|l| mine.get_turns() > l.get_turns()) {
+                longest = Some(mine);
+            }
+        }
+
+        if sealed.len() < alive.len() {
+            return false;
+        }
+
+        return match longest {
+            Some(s) => {
+                sm.get_mut(s.get_first()).score += ENDGAME_WEIGHT;
+                true
+            }
+            None => false,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    #[test]
+    fn compare_survival_needs_exact_counts_to_decide() {
+        assert_eq!(
+            compare_survival((10, true), Some((12, false))),
+            Outlast::Loss
+        );
+        assert_eq!(
+            compare_survival((12, false), Some((10, true))),
+            Outlast::Win
+        );
+        assert_eq!(
+            compare_survival((10, true), Some((10, true))),
+            Outlast::Draw
+        );
+
+        // a lower bound can't lose to a longer one, or beat a shorter one
+        assert_eq!(
+            compare_survival((10, false), Some((12, false))),
+            Outlast::Unknown
+        );
+        assert_eq!(
+            compare_survival((12, false), Some((10, false))),
+            Outlast::Unknown
+        );
+        assert_eq!(
+            compare_survival((10, false), Some((10, true))),
+            Outlast::Unknown
+        );
+
+        // opponents that can still play each other outlast any exact count
+        assert_eq!(compare_survival((20, true), None), Outlast::Loss);
+        assert_eq!(compare_survival((MAX_DEPTH, false), None), Outlast::Unknown);
+        assert_eq!(
+            compare_survival((MAX_DEPTH, false), Some((MAX_DEPTH, true))),
+            Outlast::Unknown
+        );
+    }
+
+    #[test]
+    fn moving_into_a_pocket_we_lose_in_is_penalised() {
+        // the opponent's body walls off two columns on the left and our own
+        // body closes the top, so going down leaves us six cells
+        let f = Fixture::new(
+            7,
+            7,
+            vec![
+                snake(
+                    "me",
+                    &[
+                        (1, 3),
+                        (0, 3),
+                        (0, 4),
+                        (1, 4),
+                        (1, 5),
+                        (0, 5),
+                        (0, 6),
+                        (1, 6),
+                        (2, 6),
+                        (3, 6),
+                    ],
+                    90,
+                ),
+                snake(
+                    "op",
+                    &[
+                        (4, 3),
+                        (3, 3),
+                        (3, 2),
+                        (2, 2),
+                        (2, 1),
+                        (2, 0),
+                        (3, 0),
+                        (4, 0),
+                        (5, 0),
+                        (6, 0),
+                        (6, 1),
+                        (6, 2),
+                    ],
+                    90,
+                ),
+            ],
+        );
+        let b = f.board();
+        let me = f.you();
+
+        assert!(me.is_sealed_after(Direction::Down, &b));
+        assert!(!me.is_sealed_after(Direction::Right, &b));
+
+        let mut sm = ScoredMoves::init();
+        me.avoid_own_neck(&mut sm, &b);
+        me.avoid_any_snake(&mut sm, &b);
+
+        assert!(!me.solve_endgame(&mut sm, &b));
+        assert_eq!(sm.get(Direction::Down).score, -LOSING_PENALTY);
+        assert_eq!(sm.get(Direction::Right).score, 0);
+    }
+
+    #[test]
+    fn sealed_in_whichever_way_we_go_plays_the_longest_stall() {
+        // the opponent's body walls off the two columns on the left long
+        // enough for us to circle in them, and it's about to starve
+        let f = Fixture::new(
+            7,
+            7,
+            vec![
+                snake("me", &[(0, 1), (0, 0), (1, 0)], 90),
+                snake(
+                    "op",
+                    &[
+                        (3, 0),
+                        (2, 0),
+                        (2, 1),
+                        (2, 2),
+                        (2, 3),
+                        (2, 4),
+                        (2, 5),
+                        (2, 6),
+                        (3, 6),
+                        (4, 6),
+                        (5, 6),
+                        (6, 6),
+                        (6, 5),
+                        (6, 4),
+                        (6, 3),
+                        (6, 2),
+                        (6, 1),
+                        (6, 0),
+                        (5, 0),
+                        (4, 0),
+                        (4, 1),
+                        (5, 1),
+                        (5, 2),
+                        (4, 2),
+                    ],
+                    10,
+                ),
+            ],
+        );
+        let b = f.board();
+        let me = f.you();
+
+        let mut sm = ScoredMoves::init();
+        me.avoid_own_neck(&mut sm, &b);
+        me.avoid_walls(&mut sm, &b);
+        me.avoid_any_snake(&mut sm, &b);
+
+        assert!(me.is_sealed_after(Direction::Up, &b));
+        assert!(me.is_sealed_after(Direction::Right, &b));
+
+        // both moves win, and one of them also gets the stalling bonus
+        assert!(me.solve_endgame(&mut sm, &b));
+        let scores = [sm.get(Direction::Up).score, sm.get(Direction::Right).score];
+        assert_eq!(scores.iter().min(), Some(&ENDGAME_WEIGHT));
+        assert_eq!(scores.iter().max(), Some(&(2 * ENDGAME_WEIGHT)));
+    }
+}
@@ -0,0 +1,122 @@
+// payload builders for the unit tests. coordinates are signed so a snake
+// that died on a wall can be written with its head at -1
+use serde_json::{Value, json};
+
+use super::{Battlesnake, Board, GameOver, MoveRequest};
+
+pub fn snake(id: &str, body: &[(i32, i32)], health: u16) -> Value {
+    let cells: Vec<Value> = body.iter().map(|(x, y)| json!({"x": x, "y": y})).collect();
+
+    return json!({
+        "id": id,
+        "name": id,
+        "health": health,
+        "body": cells,
+        "latency": "0",
+        "head": cells[0],
+        "length": body.len(),
+        "shout": "",
+        "squad": "",
+        "customizations": {"color": "#888888", "head": "default", "tail": "default"}
+    });
+}
+
+pub struct Fixture {
+    pub width: u16,
+    pub height: u16,
+    pub ruleset: &'static str,
+    pub turn: u16,
+    pub snakes: Vec<Value>,
+    pub food: Vec<(i32, i32)>,
+    pub hazards: Vec<(i32, i32)>,
+}
+
+impl Fixture {
+    // a standard ruleset game with the first snake as `you`
+    pub fn new(width: u16, height: u16, snakes: Vec<Value>) -> Fixture {
+        return Fixture {
+            width,
+            height,
+            ruleset: "standard",
+            turn: 0,
+            snakes,
+            food: Vec::new(),
+            hazards: Vec::new(),
+        };
+    }
+
+    pub fn with_food(mut self: Self, food: &[(i32, i32)]) -> Fixture {
+        self.food = food.to_vec();
+        return self;
+    }
+
+    pub fn with_hazards(mut self: Self, hazards: &[(i32, i32)]) -> Fixture {
+        self.hazards = hazards.to_vec();
+        return self;
+    }
+
+    pub fn with_turn(mut self: Self, turn: u16) -> Fixture {
+        self.turn = turn;
+        return self;
+    }
+
+    fn payload(self: &Self, you: Value) -> Value {
+        let cells = |cs: &[(i32, i32)]| -> Vec<Value> {
+            cs.iter().map(|(x, y)| json!({"x": x, "y": y})).collect()
+        };
+
+        return json!({
+            "game": {
+                "id": "fixture",
+                "ruleset": {
+                    "name": self.ruleset,
+                    "version": "v1",
+                    "settings": {
+                        "foodSpawnChance": 15,
+                        "minimumFood": 1,
+                        "hazardDamagePerTurn": 14,
+                        "royale": {"shrinkEveryNTurns": 0},
+                        "squad": {
+                            "allowBodyCollisions": false,
+                            "sharedElimination": false,
+                            "sharedHealth": false,
+                            "sharedLength": false
+                        }
+                    }
+                },
+                "map": "standard",
+                "timeout": 500,
+                "source": ""
+            },
+            "turn": self.turn,
+            "board": {
+                "height": self.height,
+                "width": self.width,
+                "food": cells(&self.food),
+                "hazards": cells(&self.hazards),
+                "snakes": self.snakes
+            },
+            "you": you
+        });
+    }
+
+    pub fn request(self: &Self) -> MoveRequest {
+        let mut r: MoveRequest =
+            serde_json::from_value(self.payload(self.snakes[0].clone())).unwrap();
+        r.apply_ruleset();
+        return r;
+    }
+
+    pub fn board(self: &Self) -> Board {
+        return self.request().board;
+    }
+
+    pub fn you(self: &Self) -> Battlesnake {
+        return self.request().you;
+    }
+
+    // the /end payload for a game where `you` may no longer be on the board
+    pub fn game_over(self: &Self, you: Value) -> GameOver {
+        return serde_json::from_value(self.payload(you)).unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+use super::distance::DistanceMap;
+use super::opponents::MovePredictions;
+use super::risk::{Decision, RiskProfile};
+use super::{Battlesnake, Board, Coord, flood_fill};
+
+// how far ahead of the nearest opponent counts as completely safe
+const MAX_MARGIN: i16 = 5;
+const MARGIN_WEIGHT: i16 = 4;
+const DISTANCE_WEIGHT: i16 = 2;
+const EXIT_WEIGHT: i16 = 1;
+// taken off the score of contested food in proportion to the chance we lose it
+const CONTEST_PENALTY: f32 = 20.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct FoodTarget {
+    coord: Coord,
+    distance: u16,
+    margin: i16,
+    score: i16,
+}
+
+impl FoodTarget {
+    pub fn get_coord(self: &Self) -> Coord {
+        return self.coord;
+    }
+}
+
+impl Battlesnake {
+    // food we can get to before anyone else, best first. a snake that
+    // arrives sooner takes it, and one arriving at the same time wins it (or
+    // trades heads with us) unless we're longer, if it goes for it. how often
+    // it has gone for food so far says how likely that is, and the risk
+    // profile how much we bet that it won't
+    pub fn rank_food(
+        self: &Self,
+        b: &Board,
+        dm: &DistanceMap,
+        predictions: &MovePredictions,
+        risk: &RiskProfile,
+    ) -> Vec<FoodTarget> {
+        let mut targets: Vec<FoodTarget> = Vec::new();
+
+        for f in &b.food {
+            let ours = match dm.distance(&self.id, f) {
+                Some(d) => d,
+                None => continue,
+            };
+
+            let mut lost = false;
+            // the chance nobody who'd beat us there turns up
+            let mut unopposed: f32 = 1.0;
+            let mut closest_opponent: Option<u16> = None;
+
+            for other in &b.snakes {
+                if other.id == self.id {
+                    continue;
+                }
+
+                let theirs = match dm.distance(&other.id, f) {
+                    Some(d) => d,
+                    None => continue,
+                };
+
+                if theirs < ours {
+                    lost = true;
+                    break;
+                }
+
+                if theirs == ours && other.length >= self.length {
+                    unopposed *= 1.0 - predictions.food_rate(&other.id);
+                }
+
+                closest_opponent = Some(closest_opponent.map_or(theirs, |c| c.min(theirs)));
+            }
+
+            let chance = match unopposed < 1.0 {
+                true => risk.blend(Decision::Food, 0.0, unopposed),
+                false => 1.0,
+            };
+
+            if lost || chance <= 0.0 {
+                continue;
+            }
+
+            let margin = match closest_opponent {
+                Some(d) => (d as i16 - ours as i16).min(MAX_MARGIN),
+                None => MAX_MARGIN,
+            };
+
+            // food in a corner or against a body leaves few ways out afterwards
+            let exits = f
+                .get_neighbours(b)
+                .iter()
+                .filter(|n| !n.is_in_snakeBody(b))
+                .count() as i16;
+
+            let score = margin * MARGIN_WEIGHT + exits * EXIT_WEIGHT
+                - (ours.min(i16::MAX as u16) as i16) * DISTANCE_WEIGHT
+                - ((1.0 - chance) * CONTEST_PENALTY).round() as i16;
+
+            targets.push(FoodTarget {
+                coord: *f,
+                distance: ours,
+                margin,
+                score,
+            });
+        }
+
+        targets.sort_by(|a, b| b.score.cmp(&a.score).then(a.distance.cmp(&b.distance)));
+
+        for t in &targets {
+            println!(
+                "[food {:?}]: distance {} margin {} score {}",
+                t.coord, t.distance, t.margin, t.score
+            );
+        }
+
+        return targets;
+    }
+
+    // play our snake along `path` (opponents standing still) and check that
+    // once we've eaten and our tail has stopped for a turn we can still get
+    // back to it, or at least have a body's worth of room
+    pub fn has_escape_after_eating(self: &Self, b: &Board, path: &[Coord]) -> bool {
+        let mut board = b.clone();
+
+        for step in path {
+            if *step == self.head {
+                continue;
+            }
+
+            if !step.is_in_bounds(&board) || step.is_in_snakeBody(&board) {
+                return false;
+            }
+
+            board = board.with_snake_moved(&self.id, *step);
+        }
+
+        let me = match board.snakes.iter().find(|s| s.id == self.id) {
+            Some(s) => s,
+            None => return false,
+        };
+
+        let tail = match me.body.last() {
+            Some(t) => *t,
+            None => return true,
+        };
+
+        let dm = DistanceMap::new(&board);
+        if dm.distance(&self.id, &tail).is_some() {
+            return true;
+        }
+
+        let room = me
+            .head
+            .get_neighbours(&board)
+            .iter()
+            .map(|n| flood_fill(&board, *n))
+            .max()
+            .unwrap_or(0);
+
+        return room >= me.body.len() as i16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    fn ranked(f: &Fixture, risk: &RiskProfile) -> Vec<Coord> {
+        let b = f.board();
+        let dm = DistanceMap::new(&b);
+
+        return f
+            .you()
+            .rank_food(&b, &dm, &MovePredictions::default(), risk)
+            .iter()
+            .map(|t| t.get_coord())
+            .collect();
+    }
+
+    fn at(x: u16, y: u16) -> Coord {
+        return Coord { x, y };
+    }
+
+    #[test]
+    fn food_an_opponent_gets_first_is_dropped() {
+        // (7, 5) is a tie with an equal snake, (9, 7) is theirs, and (5, 7)
+        // is closer to us than (1, 5) with the same margin
+        let f = Fixture::new(
+            11,
+            11,
+            vec![
+                snake("me", &[(5, 5), (5, 4), (5, 3)], 90),
+                snake("op", &[(9, 5), (10, 5), (10, 4)], 90),
+            ],
+        )
+        .with_food(&[(1, 5), (7, 5), (9, 7), (5, 7)]);
+
+        assert_eq!(
+            ranked(&f, &RiskProfile::default()),
+            vec![at(5, 7), at(1, 5)]
+        );
+    }
+
+    #[test]
+    fn a_tie_with_a_shorter_snake_is_ours() {
+        let f = Fixture::new(
+            11,
+            11,
+            vec![
+                snake("me", &[(5, 5), (5, 4), (5, 3)], 90),
+                snake("op", &[(9, 5), (10, 5)], 90),
+            ],
+        )
+        .with_food(&[(7, 5)]);
+
+        assert_eq!(ranked(&f, &RiskProfile::default()), vec![at(7, 5)]);
+    }
+
+    #[test]
+    fn contested_food_is_left_alone_unless_we_bet_on_it() {
+        // both of us are two moves from the food, and the same length
+        let f = Fixture::new(
+            11,
+            11,
+            vec![
+                snake("me", &[(5, 5), (5, 4), (5, 3)], 90),
+                snake("op", &[(7, 7), (7, 8), (7, 9)], 90),
+            ],
+        )
+        .with_food(&[(5, 7)]);
+
+        assert!(ranked(&f, &RiskProfile::default()).is_empty());
+        assert!(ranked(&f, &RiskProfile::parse("worst").unwrap()).is_empty());
+        assert_eq!(
+            ranked(&f, &RiskProfile::parse("expected").unwrap()),
+            vec![at(5, 7)]
+        );
+    }
+
+    #[test]
+    fn food_in_open_space_leaves_a_way_back() {
+        let f = Fixture::new(7, 7, vec![snake("me", &[(3, 3), (3, 2), (3, 1)], 90)])
+            .with_food(&[(3, 5)]);
+
+        assert!(
+            f.you()
+                .has_escape_after_eating(&f.board(), &[at(3, 3), at(3, 4), at(3, 5)])
+        );
+    }
+
+    #[test]
+    fn food_at_the_end_of_a_tunnel_is_a_trap() {
+        // w keeps the middle row blocked long after we'd have eaten, so the
+        // food is at the end of a one-wide dead end
+        let mut wall = vec![(1, 1), (2, 1), (3, 1), (4, 1), (5, 1), (6, 1)];
+        wall.extend(std::iter::repeat_n((6, 1), 8));
+
+        let f = Fixture::new(
+            7,
+            3,
+            vec![
+                snake("me", &[(0, 1), (0, 2), (1, 2), (2, 2), (3, 2)], 90),
+                snake("w", &wall, 90),
+            ],
+        )
+        .with_food(&[(3, 0)]);
+        let b = f.board();
+        let path = [at(0, 0), at(1, 0), at(2, 0), at(3, 0)];
+
+        assert!(!f.you().has_escape_after_eating(&b, &path));
+        // one step in, without eating, the tunnel still fits all of us
+        assert!(f.you().has_escape_after_eating(&b, &path[..1]));
+    }
+
+    #[test]
+    fn a_path_through_a_body_is_no_path() {
+        let f = Fixture::new(
+            7,
+            7,
+            vec![
+                snake("me", &[(3, 3), (3, 2), (3, 1)], 90),
+                snake("b", &[(3, 4), (4, 4), (5, 4)], 90),
+            ],
+        );
+
+        assert!(
+            !f.you()
+                .has_escape_after_eating(&f.board(), &[at(3, 4), at(3, 5)])
+        );
+    }
+}
@@ -0,0 +1,468 @@
+use super::{Board, Coord, Direction};
+
+// every heuristic is built on these, so they live together and are tested
+// against every cell of a range of plain and wrapped boards
+
+impl Coord {
+    // the cell one step in `dir`, wrapping across the edge on a wrapped board.
+    // None when the step would leave a normal board, or when we start off it
+    pub fn get_next_coord(self: &Self, dir: Direction, b: &Board) -> Option<Coord> {
+        if !self.is_in_bounds(b) {
+            return None;
+        }
+
+        // in bounds means width and height are at least one and every
+        // step below stays inside u16
+        let last_x = b.width - 1;
+        let last_y = b.height - 1;
+
+        match dir {
+            Direction::Left => {
+                if self.x > 0 {
+                    return Some(Coord {
+                        x: self.x - 1,
+                        y: self.y,
+                    });
+                }
+                if b.wrapped {
+                    return Some(Coord {
+                        x: last_x,
+                        y: self.y,
+                    });
+                }
+            }
+            Direction::Right => {
+                if self.x < last_x {
+                    return Some(Coord {
+                        x: self.x + 1,
+                        y: self.y,
+                    });
+                }
+                if b.wrapped {
+                    return Some(Coord { x: 0, y: self.y });
+                }
+            }
+            Direction::Down => {
+                if self.y > 0 {
+                    return Some(Coord {
+                        x: self.x,
+                        y: self.y - 1,
+                    });
+                }
+                if b.wrapped {
+                    return Some(Coord {
+                        x: self.x,
+                        y: last_y,
+                    });
+                }
+            }
+            Direction::Up => {
+                if self.y < last_y {
+                    return Some(Coord {
+                        x: self.x,
+                        y: self.y + 1,
+                    });
+                }
+                if b.wrapped {
+                    return Some(Coord { x: self.x, y: 0 });
+                }
+            }
+        }
+
+        return None;
+    }
+
+    pub fn is_in_bounds(self: &Self, b: &Board) -> bool {
+        if self.x >= b.width {
+            return false;
+        }
+
+        if self.y >= b.height {
+            return false;
+        }
+
+        return true;
+    }
+
+    // `other` is expected to be a neighbour. a neighbour that isn't one step
+    // away on the grid must be across the edge of a wrapped board
+    pub fn get_direction_to(self: &Self, other: &Coord) -> Direction {
+        if self.y == other.y {
+            if self.x > 0 && self.x - 1 == other.x {
+                return Direction::Left;
+            }
+
+            if self.x.checked_add(1) == Some(other.x) {
+                return Direction::Right;
+            }
+
+            if self.x == 0 && other.x > 1 {
+                return Direction::Left;
+            }
+
+            if other.x == 0 && self.x > 1 {
+                return Direction::Right;
+            }
+        }
+
+        if self.x == other.x {
+            if self.y > 0 && self.y - 1 == other.y {
+                return Direction::Down;
+            }
+
+            if self.y.checked_add(1) == Some(other.y) {
+                return Direction::Up;
+            }
+
+            if self.y == 0 && other.y > 1 {
+                return Direction::Down;
+            }
+
+            if other.y == 0 && self.y > 1 {
+                return Direction::Up;
+            }
+        }
+
+        return Direction::Left;
+    }
+
+    pub fn get_distance_to(self: &Self, other: &Coord) -> u16 {
+        return self
+            .x
+            .abs_diff(other.x)
+            .saturating_add(self.y.abs_diff(other.y));
+    }
+
+    // manhattan distance, but allowed to go across the edges of a wrapped board
+    pub fn get_distance_on(self: &Self, other: &Coord, b: &Board) -> u16 {
+        if !b.wrapped {
+            return self.get_distance_to(other);
+        }
+
+        let dx = self.x.abs_diff(other.x);
+        let dy = self.y.abs_diff(other.y);
+
+        return dx
+            .min(b.width.saturating_sub(dx))
+            .saturating_add(dy.min(b.height.saturating_sub(dy)));
+    }
+
+    pub fn get_neighbours(self: Self, b: &Board) -> Vec<Coord> {
+        let mut nbs: Vec<Coord> = Vec::new();
+
+        for dir in Direction::ALL {
+            // tiny wrapped boards wrap onto themselves, don't report a cell
+            // twice or as its own neighbour
+            if let Some(c) = self.get_next_coord(dir, b)
+                && c != self
+                && !nbs.contains(&c)
+            {
+                nbs.push(c);
+            }
+        }
+
+        return nbs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZES: [(u16, u16); 9] = [
+        (1, 1),
+        (1, 5),
+        (2, 2),
+        (3, 3),
+        (5, 3),
+        (7, 7),
+        (11, 11),
+        (19, 19),
+        (25, 4),
+    ];
+
+    fn board(width: u16, height: u16, wrapped: bool) -> Board {
+        return Board {
+            height,
+            width,
+            food: Vec::new(),
+            hazards: Vec::new(),
+            snakes: Vec::new(),
+            wrapped,
+            hazard_damage: 0,
+        };
+    }
+
+    fn cells(b: &Board) -> Vec<Coord> {
+        let mut all = Vec::new();
+        for x in 0..b.width {
+            for y in 0..b.height {
+                all.push(Coord { x, y });
+            }
+        }
+        return all;
+    }
+
+    fn boards() -> Vec<Board> {
+        let mut all = Vec::new();
+        for (w, h) in SIZES {
+            all.push(board(w, h, false));
+            all.push(board(w, h, true));
+        }
+        return all;
+    }
+
+    #[test]
+    fn distance_off_the_diagonal() {
+        let a = Coord { x: 0, y: 0 };
+        let b = Coord { x: 3, y: 1 };
+        assert_eq!(a.get_distance_to(&b), 4);
+        assert_eq!(b.get_distance_to(&a), 4);
+
+        let c = Coord { x: 1, y: 9 };
+        let d = Coord { x: 8, y: 2 };
+        assert_eq!(c.get_distance_to(&d), 14);
+    }
+
+    #[test]
+    fn distance_is_a_metric() {
+        for b in boards() {
+            if b.width * b.height > 121 {
+                continue;
+            }
+
+            let all = cells(&b);
+            for p in &all {
+                assert_eq!(p.get_distance_on(p, &b), 0);
+
+                for q in &all {
+                    let pq = p.get_distance_on(q, &b);
+                    assert_eq!(pq, q.get_distance_on(p, &b));
+                    assert_eq!(pq == 0, p == q);
+
+                    for r in all.iter().step_by(3) {
+                        assert!(pq <= p.get_distance_on(r, &b) + r.get_distance_on(q, &b));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn plain_distance_matches_manhattan() {
+        for b in boards().into_iter().filter(|b| !b.wrapped) {
+            for p in cells(&b) {
+                for q in cells(&b) {
+                    let expected =
+                        (p.x as i32 - q.x as i32).abs() + (p.y as i32 - q.y as i32).abs();
+                    assert_eq!(p.get_distance_to(&q) as i32, expected);
+                    assert_eq!(p.get_distance_on(&q, &b) as i32, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrapped_distance_never_exceeds_half_the_board() {
+        for b in boards().into_iter().filter(|b| b.wrapped) {
+            for p in cells(&b) {
+                for q in cells(&b) {
+                    let d = p.get_distance_on(&q, &b);
+                    assert!(d <= b.width / 2 + b.height / 2);
+                    assert!(d <= p.get_distance_to(&q));
+                }
+            }
+        }
+
+        let b = board(11, 11, true);
+        let left = Coord { x: 0, y: 5 };
+        let right = Coord { x: 10, y: 5 };
+        assert_eq!(left.get_distance_on(&right, &b), 1);
+        assert_eq!(left.get_distance_to(&right), 10);
+    }
+
+    #[test]
+    fn neighbours_are_adjacent_and_in_bounds() {
+        for b in boards() {
+            for p in cells(&b) {
+                let nbs = p.get_neighbours(&b);
+
+                for n in &nbs {
+                    assert!(n.is_in_bounds(&b));
+                    assert_eq!(p.get_distance_on(n, &b), 1);
+                    assert!(n.get_neighbours(&b).contains(&p));
+                }
+
+                for (i, n) in nbs.iter().enumerate() {
+                    assert!(!nbs[i + 1..].contains(n));
+                }
+
+                // every cell at distance one must be listed
+                let expected = cells(&b)
+                    .into_iter()
+                    .filter(|q| p.get_distance_on(q, &b) == 1)
+                    .count();
+                assert_eq!(nbs.len(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn neighbour_counts_at_edges_and_corners() {
+        let b = board(11, 11, false);
+        assert_eq!(Coord { x: 0, y: 0 }.get_neighbours(&b).len(), 2);
+        assert_eq!(Coord { x: 10, y: 10 }.get_neighbours(&b).len(), 2);
+        assert_eq!(Coord { x: 0, y: 10 }.get_neighbours(&b).len(), 2);
+        assert_eq!(Coord { x: 10, y: 0 }.get_neighbours(&b).len(), 2);
+        assert_eq!(Coord { x: 0, y: 5 }.get_neighbours(&b).len(), 3);
+        assert_eq!(Coord { x: 5, y: 10 }.get_neighbours(&b).len(), 3);
+        assert_eq!(Coord { x: 5, y: 5 }.get_neighbours(&b).len(), 4);
+
+        let w = board(11, 11, true);
+        for p in cells(&w) {
+            assert_eq!(p.get_neighbours(&w).len(), 4);
+        }
+
+        let single = board(1, 1, false);
+        assert!(Coord { x: 0, y: 0 }.get_neighbours(&single).is_empty());
+    }
+
+    #[test]
+    fn direction_to_every_neighbour() {
+        for b in boards() {
+            for p in cells(&b) {
+                for n in p.get_neighbours(&b) {
+                    let dir = p.get_direction_to(&n);
+
+                    let expected = p.get_next_coord(dir, &b).unwrap();
+
+                    assert_eq!(
+                        expected, n,
+                        "{:?} -> {:?} on {}x{}",
+                        p, n, b.width, b.height
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn next_coord_never_leaves_the_board() {
+        for b in boards() {
+            for p in cells(&b) {
+                for dir in Direction::ALL {
+                    let at_edge = match dir {
+                        Direction::Left => p.x == 0,
+                        Direction::Right => p.x == b.width - 1,
+                        Direction::Down => p.y == 0,
+                        Direction::Up => p.y == b.height - 1,
+                    };
+
+                    let next = p.get_next_coord(dir, &b);
+
+                    if !b.wrapped {
+                        assert_eq!(next.is_none(), at_edge);
+                    }
+
+                    if let Some(n) = next {
+                        assert!(n.is_in_bounds(&b));
+
+                        if n != p {
+                            assert_eq!(p.get_distance_on(&n, &b), 1);
+                            assert!(p.get_neighbours(&b).contains(&n));
+                        }
+
+                        // on a board 2 wide left and right reach the same cell
+                        let narrow = match dir {
+                            Direction::Left | Direction::Right => b.width <= 2,
+                            Direction::Down | Direction::Up => b.height <= 2,
+                        };
+                        if !narrow && n != p {
+                            assert_eq!(p.get_direction_to(&n), dir);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn next_coord_at_corners() {
+        let b = board(11, 11, false);
+        let origin = Coord { x: 0, y: 0 };
+        assert_eq!(origin.get_next_coord(Direction::Left, &b), None);
+        assert_eq!(origin.get_next_coord(Direction::Down, &b), None);
+        assert_eq!(
+            origin.get_next_coord(Direction::Up, &b),
+            Some(Coord { x: 0, y: 1 })
+        );
+
+        let top = Coord { x: 10, y: 10 };
+        assert_eq!(top.get_next_coord(Direction::Right, &b), None);
+        assert_eq!(top.get_next_coord(Direction::Up, &b), None);
+
+        let w = board(11, 11, true);
+        assert_eq!(
+            origin.get_next_coord(Direction::Left, &w),
+            Some(Coord { x: 10, y: 0 })
+        );
+        assert_eq!(
+            origin.get_next_coord(Direction::Down, &w),
+            Some(Coord { x: 0, y: 10 })
+        );
+        assert_eq!(
+            top.get_next_coord(Direction::Right, &w),
+            Some(Coord { x: 0, y: 10 })
+        );
+        assert_eq!(
+            top.get_next_coord(Direction::Up, &w),
+            Some(Coord { x: 10, y: 0 })
+        );
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let b = board(11, 7, false);
+        assert!(Coord { x: 10, y: 6 }.is_in_bounds(&b));
+        assert!(!Coord { x: 11, y: 0 }.is_in_bounds(&b));
+        assert!(!Coord { x: 0, y: 7 }.is_in_bounds(&b));
+        assert!(!Coord { x: u16::MAX, y: 3 }.is_in_bounds(&b));
+    }
+
+    #[test]
+    fn off_board_coords_have_no_neighbours() {
+        for wrapped in [false, true] {
+            let b = board(11, 11, wrapped);
+            for p in [
+                Coord { x: u16::MAX, y: 5 },
+                Coord { x: 5, y: u16::MAX },
+                Coord {
+                    x: u16::MAX,
+                    y: u16::MAX,
+                },
+                Coord { x: 11, y: 0 },
+            ] {
+                for dir in Direction::ALL {
+                    assert_eq!(p.get_next_coord(dir, &b), None);
+                }
+                assert!(p.get_neighbours(&b).is_empty());
+            }
+        }
+
+        let far = Coord {
+            x: u16::MAX,
+            y: u16::MAX,
+        };
+        assert_eq!(far.get_distance_to(&Coord { x: 0, y: 0 }), u16::MAX);
+    }
+
+    #[test]
+    fn axes_outside_u16_read_as_off_the_board() {
+        let read = |json: &str| serde_json::from_str::<Coord>(json).unwrap();
+
+        assert_eq!(read(r#"{"x": 3, "y": 4}"#), Coord { x: 3, y: 4 });
+        assert_eq!(read(r#"{"x": -1, "y": 4}"#), Coord { x: u16::MAX, y: 4 });
+        assert_eq!(read(r#"{"x": 3, "y": -7}"#), Coord { x: 3, y: u16::MAX });
+        assert_eq!(read(r#"{"x": 70000, "y": 0}"#), Coord { x: u16::MAX, y: 0 });
+    }
+}
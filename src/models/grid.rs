@@ -0,0 +1,198 @@
+use super::{Board, Coord, Direction};
+
+// flat indexing for per-cell buffers. cell i is (i % width, i / width)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Grid {
+    width: u16,
+    height: u16,
+    wrapped: bool,
+}
+
+impl Grid {
+    pub fn new(b: &Board) -> Grid {
+        return Grid {
+            width: b.width,
+            height: b.height,
+            wrapped: b.wrapped,
+        };
+    }
+
+    pub fn get_width(self: &Self) -> u16 {
+        return self.width;
+    }
+
+    pub fn get_height(self: &Self) -> u16 {
+        return self.height;
+    }
+
+    pub fn is_wrapped(self: &Self) -> bool {
+        return self.wrapped;
+    }
+
+    pub fn cells(self: &Self) -> usize {
+        return self.width as usize * self.height as usize;
+    }
+
+    pub fn index(self: &Self, c: &Coord) -> Option<usize> {
+        if c.x >= self.width || c.y >= self.height {
+            return None;
+        }
+
+        return Some(c.y as usize * self.width as usize + c.x as usize);
+    }
+
+    pub fn coord(self: &Self, i: usize) -> Coord {
+        return Coord {
+            x: (i % self.width as usize) as u16,
+            y: (i / self.width as usize) as u16,
+        };
+    }
+
+    // same cell as Coord::get_next_coord
+    pub fn step(self: &Self, i: usize, dir: Direction) -> Option<usize> {
+        let w = self.width as usize;
+        let h = self.height as usize;
+        let x = i % w;
+        let y = i / w;
+
+        return match dir {
+            Direction::Left if x > 0 => Some(i - 1),
+            Direction::Left if self.wrapped => Some(y * w + w - 1),
+            Direction::Right if x + 1 < w => Some(i + 1),
+            Direction::Right if self.wrapped => Some(y * w),
+            Direction::Down if y > 0 => Some(i - w),
+            Direction::Down if self.wrapped => Some((h - 1) * w + x),
+            Direction::Up if y + 1 < h => Some(i + w),
+            Direction::Up if self.wrapped => Some(x),
+            _ => None,
+        };
+    }
+
+    // same cells as Coord::get_neighbours, without allocating
+    pub fn neighbours(self: &Self, i: usize, out: &mut [usize; 4]) -> usize {
+        let w = self.width as usize;
+        let h = self.height as usize;
+        let x = i % w;
+        let y = i / w;
+        let mut n = 0;
+
+        let mut push = |j: usize| {
+            if j != i && !out[..n].contains(&j) {
+                out[n] = j;
+                n += 1;
+            }
+        };
+
+        if x > 0 {
+            push(i - 1);
+        } else if self.wrapped {
+            push(y * w + w - 1);
+        }
+
+        if x + 1 < w {
+            push(i + 1);
+        } else if self.wrapped {
+            push(y * w);
+        }
+
+        if y > 0 {
+            push(i - w);
+        } else if self.wrapped {
+            push((h - 1) * w + x);
+        }
+
+        if y + 1 < h {
+            push(i + w);
+        } else if self.wrapped {
+            push(x);
+        }
+
+        return n;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(width: u16, height: u16, wrapped: bool) -> Board {
+        return Board {
+            height,
+            width,
+            food: Vec::new(),
+            hazards: Vec::new(),
+            snakes: Vec::new(),
+            wrapped,
+            hazard_damage: 0,
+        };
+    }
+
+    fn boards() -> Vec<Board> {
+        let mut all = Vec::new();
+        for (w, h) in [(1, 1), (1, 4), (2, 2), (5, 3), (11, 11)] {
+            all.push(board(w, h, false));
+            all.push(board(w, h, true));
+        }
+        return all;
+    }
+
+    #[test]
+    fn indices_and_coords_round_trip() {
+        for b in boards() {
+            let grid = Grid::new(&b);
+            for i in 0..grid.cells() {
+                let c = grid.coord(i);
+                assert!(c.is_in_bounds(&b));
+                assert_eq!(grid.index(&c), Some(i));
+            }
+
+            assert_eq!(grid.index(&Coord { x: b.width, y: 0 }), None);
+            assert_eq!(grid.index(&Coord { x: 0, y: b.height }), None);
+        }
+    }
+
+    #[test]
+    fn steps_match_coords() {
+        for b in boards() {
+            let grid = Grid::new(&b);
+            for i in 0..grid.cells() {
+                let c = grid.coord(i);
+                for dir in Direction::ALL {
+                    let expected = c.get_next_coord(dir, &b).and_then(|n| grid.index(&n));
+                    assert_eq!(
+                        grid.step(i, dir),
+                        expected,
+                        "{:?} {:?} on {:?}",
+                        c,
+                        dir,
+                        grid
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn neighbours_match_coords() {
+        let mut out = [0usize; 4];
+
+        for b in boards() {
+            let grid = Grid::new(&b);
+            for i in 0..grid.cells() {
+                let c = grid.coord(i);
+                let n = grid.neighbours(i, &mut out);
+
+                let mut ours: Vec<usize> = out[..n].to_vec();
+                let mut expected: Vec<usize> = c
+                    .get_neighbours(&b)
+                    .iter()
+                    .filter_map(|n| grid.index(n))
+                    .collect();
+                ours.sort();
+                expected.sort();
+
+                assert_eq!(ours, expected, "{:?} on {:?}", c, grid);
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use super::{Battlesnake, Board, Coord, Direction, ScoredMoves};
+
+// food this close to running out is treated as an emergency
+const HEALTH_MARGIN: u16 = 2;
+// enough to beat any amount of space or territory, never a certain death
+const EMERGENCY_WEIGHT: i16 = 2000;
+
+impl Battlesnake {
+    // health spent walking `path`: one per move plus hazard damage for every
+    // hazard on a cell we step onto. stacked hazards hurt once each
+    pub fn get_path_cost(self: &Self, b: &Board, path: &[Coord]) -> u16 {
+        let mut cost: u16 = 0;
+
+        for step in path {
+            if *step == self.head {
+                continue;
+            }
+
+            let hazards = b.hazards.iter().filter(|h| *h == step).count() as u16;
+            cost = cost
+                .saturating_add(1)
+                .saturating_add(hazards.saturating_mul(b.hazard_damage));
+        }
+
+        return cost;
+    }
+
+    // the food we can get to for the least health, with the path and its
+    // cost. food is eaten before starvation is checked, so only the moves
+    // before the last one have to leave us alive
+    pub fn find_cheapest_food(self: &Self, b: &Board) -> Option<(Vec<Coord>, u16)> {
+        let mut best: Option<(Vec<Coord>, u16)> = None;
+
+        for f in &b.food {
+            let path = match self.find_path_to(b, f) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            // A* walks through bodies when it has to, we can't
+            if path.iter().any(|c| *c != self.head && c.is_in_snakeBody(b)) {
+                continue;
+            }
+
+            let cost = self.get_path_cost(b, &path);
+            let before_eating = match path.len() {
+                0 | 1 => 0,
+                n => self.get_path_cost(b, &path[..n - 1]),
+            };
+
+            if before_eating >= self.health {
+                continue;
+            }
+
+            if best.as_ref().is_none_or(|(_, c)| cost < *c) {
+                best = Some((path, cost));
+            }
+        }
+
+        return best;
+    }
+
+    pub fn is_starving(self: &Self, b: &Board) -> bool {
+        return match self.find_cheapest_food(b) {
+            Some((_, cost)) => self.health <= cost.saturating_add(HEALTH_MARGIN),
+            None => false,
+        };
+    }
+
+    // emergency mode: the first step toward the cheapest food outweighs
+    // everything except moves already known to kill us
+    pub fn move_toward_food_urgently(self: &Self, sm: &mut ScoredMoves, b: &Board) {
+        let (path, cost) = match self.find_cheapest_food(b) {
+            Some(p) => p,
+            None => return,
+        };
+
+        let next = match path.iter().find(|c| **c != self.head) {
+            Some(c) => *c,
+            None => return,
+        };
+
+        for dir in Direction::ALL {
+            if self.head.get_next_coord(dir, b) == Some(next) && !sm.is_dead(dir) {
+                sm.get_mut(dir).score += EMERGENCY_WEIGHT;
+                println!(
+                    "[starving]: health {} food costs {}, going {}",
+                    self.health,
+                    cost,
+                    dir.get_str()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    // food three moves left through two hazards, or four moves right
+    fn corridor(health: u16) -> Fixture {
+        return Fixture::new(9, 1, vec![snake("me", &[(4, 0), (4, 0)], health)])
+            .with_food(&[(1, 0), (8, 0)])
+            .with_hazards(&[(2, 0), (3, 0)]);
+    }
+
+    fn at(x: u16, y: u16) -> Coord {
+        return Coord { x, y };
+    }
+
+    #[test]
+    fn hazards_add_to_the_cost_of_a_path() {
+        let f = Fixture::new(5, 1, vec![snake("me", &[(0, 0)], 90)]).with_hazards(&[
+            (2, 0),
+            (3, 0),
+            (3, 0),
+        ]);
+        let b = f.board();
+        let me = f.you();
+        let damage = b.hazard_damage;
+
+        assert_eq!(me.get_path_cost(&b, &[at(0, 0), at(1, 0)]), 1);
+        assert_eq!(
+            me.get_path_cost(&b, &[at(0, 0), at(1, 0), at(2, 0)]),
+            2 + damage
+        );
+        assert_eq!(
+            me.get_path_cost(&b, &[at(1, 0), at(2, 0), at(3, 0)]),
+            3 + 3 * damage
+        );
+    }
+
+    #[test]
+    fn the_cheapest_food_can_be_further_away() {
+        let f = corridor(90);
+        let (path, cost) = f.you().find_cheapest_food(&f.board()).unwrap();
+        assert_eq!(path.last(), Some(&at(8, 0)));
+        assert_eq!(cost, 4);
+    }
+
+    #[test]
+    fn food_we_starve_on_the_way_to_is_out_of_reach() {
+        let f = corridor(3);
+        assert!(f.you().find_cheapest_food(&f.board()).is_none());
+
+        // eating happens before starving, so the last move can be free
+        let f = corridor(4);
+        assert!(f.you().find_cheapest_food(&f.board()).is_some());
+    }
+
+    #[test]
+    fn starving_leaves_a_margin() {
+        let cost = 4;
+        let f = corridor(cost + HEALTH_MARGIN);
+        assert!(f.you().is_starving(&f.board()));
+
+        let f = corridor(cost + HEALTH_MARGIN + 1);
+        assert!(!f.you().is_starving(&f.board()));
+    }
+
+    #[test]
+    fn an_emergency_outweighs_everything_else() {
+        let f = corridor(5);
+        let b = f.board();
+
+        let mut sm = ScoredMoves::init();
+        f.you().move_toward_food_urgently(&mut sm, &b);
+
+        assert_eq!(sm.get(Direction::Right).score, EMERGENCY_WEIGHT);
+        assert_eq!(sm.get(Direction::Left).score, 0);
+    }
+}
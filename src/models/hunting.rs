@@ -0,0 +1,187 @@
+use super::distance::DistanceMap;
+use super::opponents::MovePredictions;
+use super::{Battlesnake, Board, Coord, ScoredMoves};
+
+// weight per segment we're longer than the target, capped so hunting never
+// outweighs staying alive
+const HUNT_WEIGHT: i16 = 25;
+const MAX_HUNT_WEIGHT: i16 = 150;
+
+pub struct HuntTarget<'a> {
+    snake: &'a Battlesnake,
+    aim: Coord,
+    distance: u16,
+    deficit: u16,
+}
+
+impl Battlesnake {
+    // where we expect this snake's head to be after its next move
+    pub fn predict_next_head(self: &Self, predictions: &MovePredictions) -> Coord {
+        return predictions.most_likely_move(&self.id).unwrap_or(self.head);
+    }
+
+    // every shorter opponent, best target first. a big length deficit makes
+    // a snake worth chasing further, each move of distance costs half a segment
+    pub fn rank_hunt_targets<'a>(
+        self: &Self,
+        b: &'a Board,
+        dm: &DistanceMap,
+        predictions: &MovePredictions,
+    ) -> Vec<HuntTarget<'a>> {
+        let mut targets: Vec<HuntTarget> = Vec::new();
+
+        for other in &b.snakes {
+            if other.id == self.id || other.length >= self.length {
+                continue;
+            }
+
+            let aim = other.predict_next_head(predictions);
+            let distance = dm
+                .distance(&self.id, &aim)
+                .unwrap_or(self.head.get_distance_on(&aim, b));
+
+            targets.push(HuntTarget {
+                snake: other,
+                aim,
+                distance,
+                deficit: self.length - other.length,
+            });
+        }
+
+        targets.sort_by_key(|t| t.distance as i32 - 2 * t.deficit as i32);
+
+        return targets;
+    }
+
+    // chase the predicted head of the best target. arriving there at the
+    // same time as them is a head to head we win
+    pub fn hunt_smaller_snakes(
+        self: &Self,
+        sm: &mut ScoredMoves,
+        b: &Board,
+        dm: &DistanceMap,
+        predictions: &MovePredictions,
+    ) {
+        for target in self.rank_hunt_targets(b, dm, predictions) {
+            let path = match self.find_path_to(b, &target.aim) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            let deficit = target.deficit.min(i16::MAX as u16) as i16;
+            let weight = HUNT_WEIGHT.saturating_mul(deficit).min(MAX_HUNT_WEIGHT);
+
+            println!(
+                "[hunt {}]: aiming at {:?}, {} away, {} shorter, weight {}",
+                target.snake.name, target.aim, target.distance, target.deficit, weight
+            );
+
+            self.follow_path_with_weight(sm, path, weight);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Direction;
+    use crate::models::fixtures::{Fixture, snake};
+    use crate::models::opponents::GameHistory;
+
+    fn field() -> Fixture {
+        return Fixture::new(
+            11,
+            11,
+            vec![
+                snake(
+                    "me",
+                    &[
+                        (1, 5),
+                        (1, 4),
+                        (1, 3),
+                        (1, 2),
+                        (1, 1),
+                        (1, 0),
+                        (0, 0),
+                        (0, 1),
+                    ],
+                    90,
+                ),
+                // one shorter and close by
+                snake(
+                    "a",
+                    &[(4, 5), (5, 5), (6, 5), (7, 5), (8, 5), (9, 5), (10, 5)],
+                    90,
+                ),
+                // far away but tiny
+                snake("c", &[(9, 9), (10, 9)], 90),
+                // as long as us
+                snake(
+                    "d",
+                    &[
+                        (0, 9),
+                        (0, 10),
+                        (1, 10),
+                        (2, 10),
+                        (3, 10),
+                        (4, 10),
+                        (5, 10),
+                        (6, 10),
+                    ],
+                    90,
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn a_big_deficit_is_worth_a_longer_chase() {
+        let f = field();
+        let b = f.board();
+        let me = f.you();
+        let dm = DistanceMap::new(&b);
+        let predictions = GameHistory::default().predict(&b, &me);
+
+        let ids: Vec<&str> = me
+            .rank_hunt_targets(&b, &dm, &predictions)
+            .iter()
+            .map(|t| t.snake.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["c", "a"]);
+    }
+
+    #[test]
+    fn we_aim_where_the_head_is_going() {
+        let f = field();
+        let b = f.board();
+        let me = f.you();
+        let a = &b.snakes[1];
+
+        assert_eq!(a.predict_next_head(&MovePredictions::default()), a.head);
+
+        let predictions = GameHistory::default().predict(&b, &me);
+        let aim = a.predict_next_head(&predictions);
+        assert_eq!(a.head.get_distance_on(&aim, &b), 1);
+    }
+
+    #[test]
+    fn the_chase_weight_is_capped() {
+        let f = field();
+        let b = f.board();
+        let me = f.you();
+        let dm = DistanceMap::new(&b);
+        let predictions = GameHistory::default().predict(&b, &me);
+
+        let mut sm = ScoredMoves::init();
+        me.hunt_smaller_snakes(&mut sm, &b, &dm, &predictions);
+
+        // c is up and to the right, and six segments shorter
+        let scores: Vec<i16> = Direction::ALL.iter().map(|d| sm.get(*d).score).collect();
+        assert_eq!(scores.iter().sum::<i16>(), MAX_HUNT_WEIGHT);
+        assert_eq!(
+            sm.get(Direction::Up).score + sm.get(Direction::Right).score,
+            MAX_HUNT_WEIGHT
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use super::opponents::MovePredictions;
+use super::risk::{Decision, RiskProfile};
+use super::state::GameState;
+use super::{Battlesnake, Board, Direction, ScoredMoves};
+
+// heads this close can both step onto the same cell
+const INTERACTION_RANGE: u16 = 2;
+// opponents beyond this many are left to their most likely move
+const MAX_PLAYERS: usize = 2;
+const FICTITIOUS_PLAY_ROUNDS: usize = 2000;
+
+const LOSS: f32 = -1000.0;
+// dying alongside a snake is better than dying alone
+const TRADE_CREDIT: f32 = 200.0;
+const KILL_REWARD: f32 = 300.0;
+const NASH_WEIGHT: f32 = 200.0;
+
+// the row player's mixed strategy for the zero sum game `payoff`, where we
+// pick the row and the opponents together pick the column. fictitious play:
+// both sides keep best responding to everything the other has played so far,
+// and the frequencies converge to an equilibrium
+pub fn solve_zero_sum(payoff: &[Vec<f32>]) -> Vec<f32> {
+    let rows = payoff.len();
+    let cols = payoff.first().map_or(0, |r| r.len());
+    if rows == 0 || cols == 0 {
+        return vec![0.0; rows];
+    }
+
+    let mut row_counts = vec![0u32; rows];
+    let mut col_counts = vec![0u32; cols];
+    // what each row has earned against the columns played so far, and what
+    // each column has conceded against the rows
+    let mut row_totals = vec![0.0f32; rows];
+    let mut col_totals = vec![0.0f32; cols];
+
+    let mut row = 0;
+    for _ in 0..FICTITIOUS_PLAY_ROUNDS {
+        row_counts[row] += 1;
+        for (c, total) in col_totals.iter_mut().enumerate() {
+            *total += payoff[row][c];
+        }
+
+        let col = argmin(&col_totals);
+        col_counts[col] += 1;
+        for (r, total) in row_totals.iter_mut().enumerate() {
+            *total += payoff[r][col];
+        }
+
+        row = argmax(&row_totals);
+    }
+
+    let played: u32 = row_counts.iter().sum();
+    return row_counts
+        .iter()
+        .map(|c| *c as f32 / played as f32)
+        .collect();
+}
+
+fn argmax(v: &[f32]) -> usize {
+    let mut best = 0;
+    for i in 1..v.len() {
+        if v[i] > v[best] {
+            best = i;
+        }
+    }
+    return best;
+}
+
+fn argmin(v: &[f32]) -> usize {
+    let mut best = 0;
+    for i in 1..v.len() {
+        if v[i] < v[best] {
+            best = i;
+        }
+    }
+    return best;
+}
+
+impl Battlesnake {
+    // what the position after one joint move is worth to us. only deaths
+    // we caused count, a snake that starves or hits someone else would have
+    // died whatever we did
+    fn evaluate_joint_move(
+        self: &Self,
+        state: &mut GameState,
+        me: usize,
+        players: &[usize],
+    ) -> f32 {
+        let killed = players
+            .iter()
+            .filter(|p| state.is_killed_by(**p, me))
+            .count() as f32;
+
+        if !state.is_alive(me) {
+            return LOSS + killed * TRADE_CREDIT;
+        }
+
+        // room beyond twice our length makes no difference this close up
+        let space = state.count_space(me, state.get_length(me) * 2) as f32;
+        return killed * KILL_REWARD + space;
+    }
+
+    // when our head and an opponent's can meet, scoring each of our moves
+    // on its own misses that the best move depends on theirs. play every
+    // joint move, then favour our moves by how often the equilibrium plays
+    // them when the nearby opponents are one side trying to hurt us, and
+    // by whether they're the best answer to the moves we predict for them,
+    // as the risk profile says. returns false when no opponent is close
+    // enough for it to matter. `state` is `b`'s, and is left as it was found
+    pub fn play_head_to_heads(
+        self: &Self,
+        sm: &mut ScoredMoves,
+        b: &Board,
+        state: &mut GameState,
+        predictions: &MovePredictions,
+        risk: &RiskProfile,
+    ) -> bool {
+        let me = match b.snakes.iter().position(|s| s.id == self.id) {
+            Some(i) => i,
+            None => return false,
+        };
+
+        let mut players: Vec<usize> = (0..b.snakes.len())
+            .filter(|s| {
+                *s != me && b.snakes[*s].head.get_distance_on(&self.head, b) <= INTERACTION_RANGE
+            })
+            .collect();
+
+        if players.is_empty() {
+            return false;
+        }
+
+        players.sort_by_key(|s| b.snakes[*s].head.get_distance_on(&self.head, b));
+        players.truncate(MAX_PLAYERS);
+
+        if !state.is_alive(me) {
+            return false;
+        }
+
+        let ours: Vec<Direction> = Direction::ALL
+            .iter()
+            .copied()
+            .filter(|d| !sm.is_dead(*d))
+            .collect();
+
+        if ours.is_empty() {
+            return false;
+        }
+
+        // everyone else makes the move we expect of them
+        let mut out = [Direction::Up; 4];
+        let mut moves: Vec<Direction> = b
+            .snakes
+            .iter()
+            .enumerate()
+            .map(|(s, snake)| {
+                if let Some(c) = predictions.most_likely_move(&snake.id) {
+                    return snake.head.get_direction_to(&c);
+                }
+                match state.legal_moves(s, &mut out) {
+                    0 => Direction::Up,
+                    _ => out[0],
+                }
+            })
+            .collect();
+
+        let options: Vec<Vec<Direction>> = players
+            .iter()
+            .map(|p| {
+                let n = state.legal_moves(*p, &mut out);
+                if n == 0 {
+                    return vec![Direction::Up];
+                }
+                out[..n].to_vec()
+            })
+            .collect();
+
+        let columns: usize = options.iter().map(|o| o.len()).product();
+        let mut payoff = vec![vec![0.0f32; columns]; ours.len()];
+
+        for (r, dir) in ours.iter().enumerate() {
+            moves[me] = *dir;
+
+            for (col, cell) in payoff[r].iter_mut().enumerate() {
+                // column number as a mixed radix over the players' options
+                let mut rest = col;
+                for (p, player) in players.iter().enumerate() {
+                    moves[*player] = options[p][rest % options[p].len()];
+                    rest /= options[p].len();
+                }
+
+                state.make_moves(&moves);
+                *cell = self.evaluate_joint_move(state, me, &players);
+                state.unmake();
+            }
+        }
+
+        let strategy = solve_zero_sum(&payoff);
+
+        // how likely each column is, one player's options at a time
+        let chances: Vec<Vec<f32>> = players
+            .iter()
+            .zip(&options)
+            .map(|(p, dirs)| {
+                let snake = &b.snakes[*p];
+                let raw: Vec<f32> = dirs
+                    .iter()
+                    .map(|d| {
+                        snake
+                            .head
+                            .get_next_coord(*d, b)
+                            .and_then(|c| predictions.probability(&snake.id, &c))
+                            .unwrap_or(1.0)
+                    })
+                    .collect();
+
+                let total: f32 = raw.iter().sum();
+                if total <= 0.0 {
+                    return vec![1.0 / dirs.len() as f32; dirs.len()];
+                }
+                raw.iter().map(|r| r / total).collect()
+            })
+            .collect();
+
+        let expected: Vec<f32> = payoff
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(col, v)| {
+                        let mut rest = col;
+                        let mut p = 1.0;
+                        for (i, c) in chances.iter().enumerate() {
+                            p *= c[rest % options[i].len()];
+                            rest /= options[i].len();
+                        }
+                        p * v
+                    })
+                    .sum()
+            })
+            .collect();
+
+        let best_response = argmax(&expected);
+
+        for (r, dir) in ours.iter().enumerate() {
+            let worst = payoff[r].iter().copied().fold(f32::MAX, f32::min);
+            let likely = if r == best_response { 1.0 } else { 0.0 };
+            let share = risk.blend(Decision::Nash, strategy[r], likely);
+
+            sm.get_mut(*dir).score += (share * NASH_WEIGHT).round() as i16;
+            println!(
+                "[nash {}]: plays {:.2}, worst case {}, expected {:.0}",
+                dir.get_str(),
+                strategy[r],
+                worst,
+                expected[r]
+            );
+        }
+
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    #[test]
+    fn matching_pennies_is_an_even_split() {
+        let payoff = vec![vec![1.0, -1.0], vec![-1.0, 1.0]];
+        let strategy = solve_zero_sum(&payoff);
+
+        assert_eq!(strategy.len(), 2);
+        for p in strategy {
+            assert!((p - 0.5).abs() < 0.02, "{}", p);
+        }
+    }
+
+    #[test]
+    fn a_dominant_row_is_always_played() {
+        let payoff = vec![
+            vec![1.0, 2.0, 0.5],
+            vec![3.0, 4.0, 2.0],
+            vec![0.0, 1.0, 1.0],
+        ];
+        let strategy = solve_zero_sum(&payoff);
+
+        assert!(strategy[1] > 0.99, "{:?}", strategy);
+    }
+
+    fn evaluate(f: Fixture, moves: [Direction; 2]) -> f32 {
+        let b = f.board();
+        let mut state = GameState::new(&b);
+        state.make_moves(&moves);
+        return f.you().evaluate_joint_move(&mut state, 0, &[1]);
+    }
+
+    #[test]
+    fn only_kills_we_cause_are_credited() {
+        use Direction::{Down, Left, Right, Up};
+
+        let me = snake("me", &[(1, 1), (0, 1), (0, 0)], 90);
+
+        // the opponent starves on a move that has nothing to do with us
+        let starving = Fixture::new(7, 7, vec![me.clone(), snake("b", &[(5, 5), (5, 4)], 1)]);
+        assert!(evaluate(starving, [Up, Up]) < KILL_REWARD);
+
+        // a shorter snake meets our head
+        let h2h = Fixture::new(7, 7, vec![me, snake("b", &[(3, 1), (4, 1)], 90)]);
+        assert!(evaluate(h2h, [Right, Left]) >= KILL_REWARD);
+
+        // and one runs into our body
+        let body = Fixture::new(
+            7,
+            7,
+            vec![
+                snake("me", &[(2, 2), (1, 2), (0, 2)], 90),
+                snake("b", &[(1, 3), (1, 4), (1, 5)], 90),
+            ],
+        );
+        assert!(evaluate(body, [Right, Down]) >= KILL_REWARD);
+    }
+}
@@ -0,0 +1,110 @@
+use super::{Battlesnake, Board, Direction, ScoredMoves, flood_fill};
+
+// only snakes this close to our head can be cut off by our next move
+const TRAP_RANGE: u16 = 4;
+const TRAP_REWARD: i16 = 120;
+// a smaller bonus per cell we take away from a snake we can't quite seal
+const SQUEEZE_WEIGHT: i16 = 2;
+
+impl Battlesnake {
+    // the most room this snake could reach with any one of its moves
+    pub fn get_best_escape(self: &Self, b: &Board) -> i16 {
+        return self
+            .head
+            .get_neighbours(b)
+            .iter()
+            .map(|n| flood_fill(b, *n))
+            .max()
+            .unwrap_or(0);
+    }
+
+    // look at the board from each nearby opponent's side after each of our
+    // moves. a move that leaves them less room than their own length is a
+    // trap they can't get out of, so reward it, as long as we aren't sealing
+    // ourselves in too
+    pub fn use_cut_offs(self: &Self, sm: &mut ScoredMoves, b: &Board) {
+        let nearby: Vec<&Battlesnake> = b
+            .snakes
+            .iter()
+            .filter(|s| s.id != self.id && s.head.get_distance_on(&self.head, b) <= TRAP_RANGE)
+            .collect();
+
+        if nearby.is_empty() {
+            return;
+        }
+
+        // how much room each of them has now doesn't depend on our move
+        let escapes: Vec<i16> = nearby.iter().map(|o| o.get_best_escape(b)).collect();
+
+        for dir in Direction::ALL {
+            if sm.is_dead(dir) {
+                continue;
+            }
+
+            let next = match self.head.get_next_coord(dir, b) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            if flood_fill(b, next) < self.body.len() as i16 {
+                continue;
+            }
+
+            let moved = b.with_snake_moved(&self.id, next);
+
+            for (opponent, &before) in nearby.iter().zip(&escapes) {
+                let length = opponent.body.len() as i16;
+
+                // already stuck, no credit for it
+                if before < length {
+                    continue;
+                }
+
+                let after = match moved.snakes.iter().find(|s| s.id == opponent.id) {
+                    Some(s) => s.get_best_escape(&moved),
+                    None => continue,
+                };
+
+                if after < length {
+                    sm.get_mut(dir).score += TRAP_REWARD;
+                    println!(
+                        "[trap {}]: {} left with {} cells, needs {}",
+                        dir.get_str(),
+                        opponent.name,
+                        after,
+                        length
+                    );
+                } else if after < before {
+                    sm.get_mut(dir).score += (before - after).min(20) * SQUEEZE_WEIGHT;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    #[test]
+    fn sealing_a_snake_into_a_corner_is_rewarded() {
+        let f = Fixture::new(
+            7,
+            7,
+            vec![
+                snake("me", &[(2, 1), (1, 1), (1, 2), (1, 3), (1, 4), (1, 5)], 90),
+                snake("b", &[(0, 0), (0, 1), (0, 2), (0, 3)], 90),
+            ],
+        );
+        let b = f.board();
+        let me = f.you();
+
+        let mut sm = ScoredMoves::init();
+        me.avoid_own_neck(&mut sm, &b);
+        me.use_cut_offs(&mut sm, &b);
+
+        assert_eq!(sm.get(Direction::Down).score, TRAP_REWARD);
+        assert_eq!(sm.get(Direction::Right).score, 0);
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+
+use super::{Battlesnake, Board, Coord};
+
+// running tallies of how an opponent has behaved when it had a choice.
+// each tendency is stored as (times it happened, times it could have happened)
+#[derive(Debug, Default, Clone)]
+pub struct OpponentModel {
+    food_moves: u16,
+    food_opportunities: u16,
+    h2h_avoided: u16,
+    h2h_opportunities: u16,
+    aggressive_moves: u16,
+    aggressive_opportunities: u16,
+}
+
+impl OpponentModel {
+    // laplace smoothed so an unseen snake sits at 0.5 for every tendency
+    fn rate(hits: u16, opportunities: u16) -> f32 {
+        return (hits as f32 + 1.0) / (opportunities as f32 + 2.0);
+    }
+
+    pub fn food_rate(self: &Self) -> f32 {
+        return OpponentModel::rate(self.food_moves, self.food_opportunities);
+    }
+
+    pub fn h2h_avoidance_rate(self: &Self) -> f32 {
+        return OpponentModel::rate(self.h2h_avoided, self.h2h_opportunities);
+    }
+
+    pub fn aggression_rate(self: &Self) -> f32 {
+        return OpponentModel::rate(self.aggressive_moves, self.aggressive_opportunities);
+    }
+}
+
+// what a single candidate move looks like from the mover's point of view
+struct MoveFeatures {
+    toward_food: bool,
+    near_larger_head: bool,
+    toward_smaller_head: bool,
+}
+
+fn closest_distance(from: &Coord, targets: &[Coord], b: &Board) -> Option<u16> {
+    return targets.iter().map(|t| from.get_distance_on(t, b)).min();
+}
+
+fn is_safe_for(c: &Coord, b: &Board) -> bool {
+    return c.is_in_bounds(b) && !c.is_in_snakeBody(b);
+}
+
+fn candidate_moves(snake: &Battlesnake, b: &Board) -> Vec<Coord> {
+    return snake
+        .head
+        .get_neighbours(b)
+        .into_iter()
+        .filter(|c| is_safe_for(c, b))
+        .collect();
+}
+
+fn describe_move(snake: &Battlesnake, to: &Coord, b: &Board) -> MoveFeatures {
+    let food_before = closest_distance(&snake.head, &b.food, b);
+    let food_after = closest_distance(to, &b.food, b);
+
+    let toward_food = match (food_before, food_after) {
+        (Some(before), Some(after)) => after < before,
+        _ => false,
+    };
+
+    let mut near_larger_head = false;
+    let mut smaller_heads: Vec<Coord> = Vec::new();
+
+    for other in &b.snakes {
+        if other.id == snake.id {
+            continue;
+        }
+
+        if other.length >= snake.length {
+            if to.get_distance_on(&other.head, b) == 1 {
+                near_larger_head = true;
+            }
+        } else {
+            smaller_heads.push(other.head);
+        }
+    }
+
+    let toward_smaller_head = match (
+        closest_distance(&snake.head, &smaller_heads, b),
+        closest_distance(to, &smaller_heads, b),
+    ) {
+        (Some(before), Some(after)) => after < before,
+        _ => false,
+    };
+
+    return MoveFeatures {
+        toward_food,
+        near_larger_head,
+        toward_smaller_head,
+    };
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct GameHistory {
+    last_turn: Option<u16>,
+    last_board: Option<Board>,
+    models: HashMap<String, OpponentModel>,
+}
+
+impl GameHistory {
+    // compare the previous board with this one to work out which way every
+    // opponent moved, then update their model with what they chose
+    pub fn observe(self: &mut Self, turn: u16, b: &Board, me: &Battlesnake) {
+        if let (Some(last_turn), Some(prev)) = (self.last_turn, &self.last_board)
+            && turn == last_turn + 1
+        {
+            for before in &prev.snakes {
+                if before.id == me.id {
+                    continue;
+                }
+
+                let after = match b.snakes.iter().find(|s| s.id == before.id) {
+                    Some(s) => s,
+                    None => continue,
+                };
+
+                if before.head.get_distance_on(&after.head, b) != 1 {
+                    continue;
+                }
+
+                let model = self.models.entry(before.id.clone()).or_default();
+                model.record(before, &after.head, prev);
+            }
+        }
+
+        self.last_turn = Some(turn);
+        self.last_board = Some(b.clone());
+    }
+
+    // the last turn we were asked to move on, and so were still alive for
+    pub fn get_last_turn(self: &Self) -> Option<u16> {
+        return self.last_turn;
+    }
+
+    pub fn get_last_board(self: &Self) -> Option<&Board> {
+        return self.last_board.as_ref();
+    }
+
+    pub fn predict(self: &Self, b: &Board, me: &Battlesnake) -> MovePredictions {
+        let mut predictions = MovePredictions::default();
+
+        for snake in &b.snakes {
+            if snake.id == me.id {
+                continue;
+            }
+
+            let model = self.models.get(&snake.id).cloned().unwrap_or_default();
+            predictions
+                .moves
+                .insert(snake.id.clone(), model.predict(snake, b));
+            predictions
+                .food_rates
+                .insert(snake.id.clone(), model.food_rate());
+        }
+
+        return predictions;
+    }
+}
+
+impl OpponentModel {
+    fn record(self: &mut Self, snake: &Battlesnake, moved_to: &Coord, b: &Board) {
+        let candidates = candidate_moves(snake, b);
+        let features: Vec<MoveFeatures> = candidates
+            .iter()
+            .map(|c| describe_move(snake, c, b))
+            .collect();
+        let chosen = describe_move(snake, moved_to, b);
+
+        // only count a tendency when the snake actually had a choice about it
+        if features.iter().any(|f| f.toward_food) && features.iter().any(|f| !f.toward_food) {
+            self.food_opportunities += 1;
+            if chosen.toward_food {
+                self.food_moves += 1;
+            }
+        }
+
+        if features.iter().any(|f| f.near_larger_head)
+            && features.iter().any(|f| !f.near_larger_head)
+        {
+            self.h2h_opportunities += 1;
+            if !chosen.near_larger_head {
+                self.h2h_avoided += 1;
+            }
+        }
+
+        if features.iter().any(|f| f.toward_smaller_head)
+            && features.iter().any(|f| !f.toward_smaller_head)
+        {
+            self.aggressive_opportunities += 1;
+            if chosen.toward_smaller_head {
+                self.aggressive_moves += 1;
+            }
+        }
+    }
+
+    // naive bayes style: each tendency multiplies the moves that share the
+    // feature by its rate and the ones that don't by the complement
+    fn predict(self: &Self, snake: &Battlesnake, b: &Board) -> Vec<(Coord, f32)> {
+        let candidates = candidate_moves(snake, b);
+        let features: Vec<MoveFeatures> = candidates
+            .iter()
+            .map(|c| describe_move(snake, c, b))
+            .collect();
+
+        let food = self.food_rate();
+        let avoid = self.h2h_avoidance_rate();
+        let aggression = self.aggression_rate();
+
+        let mut weights: Vec<f32> = features
+            .iter()
+            .map(|f| {
+                let mut w = 1.0;
+                w *= if f.toward_food { food } else { 1.0 - food };
+                w *= if f.near_larger_head {
+                    1.0 - avoid
+                } else {
+                    avoid
+                };
+                w *= if f.toward_smaller_head {
+                    aggression
+                } else {
+                    1.0 - aggression
+                };
+                w
+            })
+            .collect();
+
+        let total: f32 = weights.iter().sum();
+        if total > 0.0 {
+            for w in weights.iter_mut() {
+                *w /= total;
+            }
+        }
+
+        return candidates.into_iter().zip(weights).collect();
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MovePredictions {
+    moves: HashMap<String, Vec<(Coord, f32)>>,
+    food_rates: HashMap<String, f32>,
+}
+
+impl MovePredictions {
+    // how often the snake heads for food when it has the choice
+    pub fn food_rate(self: &Self, id: &str) -> f32 {
+        return match self.food_rates.get(id) {
+            Some(r) => *r,
+            None => OpponentModel::default().food_rate(),
+        };
+    }
+
+    pub fn probability(self: &Self, id: &str, c: &Coord) -> Option<f32> {
+        let moves = self.moves.get(id)?;
+        return Some(
+            moves
+                .iter()
+                .find(|(m, _)| m == c)
+                .map(|(_, p)| *p)
+                .unwrap_or(0.0),
+        );
+    }
+
+    pub fn most_likely_move(self: &Self, id: &str) -> Option<Coord> {
+        let moves = self.moves.get(id)?;
+        return moves
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(c, _)| *c);
+    }
+
+    // how dangerous `c` is relative to assuming the snake picks any of its
+    // moves at random. 1.0 is the old "every square is equally likely" answer
+    pub fn weight(self: &Self, id: &str, c: &Coord) -> f32 {
+        let moves = match self.moves.get(id) {
+            Some(m) => m,
+            None => return 1.0,
+        };
+
+        if moves.is_empty() {
+            return 1.0;
+        }
+
+        let p = self.probability(id, c).unwrap_or(0.0);
+        return p * moves.len() as f32;
+    }
+
+    // the same scale as weight, if the snake were sure to step onto `c`
+    // whenever it's one of its moves
+    pub fn worst_case_weight(self: &Self, id: &str, c: &Coord) -> f32 {
+        let moves = match self.moves.get(id) {
+            Some(m) if !m.is_empty() => m,
+            _ => return 1.0,
+        };
+
+        if !moves.iter().any(|(m, _)| m == c) {
+            return 0.0;
+        }
+
+        return moves.len() as f32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    // op walking straight up toward the food at the top, `step` moves in
+    fn walk(step: i32) -> Fixture {
+        let op: Vec<(i32, i32)> = (0..3).map(|i| (3, 3 + step - i)).collect();
+
+        return Fixture::new(
+            7,
+            9,
+            vec![snake("me", &[(0, 0), (1, 0)], 90), snake("op", &op, 90)],
+        )
+        .with_food(&[(3, 8)])
+        .with_turn(step as u16);
+    }
+
+    fn watch(turns: &[i32]) -> GameHistory {
+        let mut history = GameHistory::default();
+        for t in turns {
+            let f = walk(*t);
+            history.observe(*t as u16, &f.board(), &f.you());
+        }
+        return history;
+    }
+
+    #[test]
+    fn unseen_snakes_sit_in_the_middle() {
+        let model = OpponentModel::default();
+        assert_eq!(model.food_rate(), 0.5);
+        assert_eq!(model.h2h_avoidance_rate(), 0.5);
+        assert_eq!(model.aggression_rate(), 0.5);
+
+        assert_eq!(MovePredictions::default().food_rate("op"), 0.5);
+    }
+
+    #[test]
+    fn going_for_food_raises_the_food_rate() {
+        let f = walk(3);
+        let predictions = watch(&[0, 1, 2, 3]).predict(&f.board(), &f.you());
+
+        // three moves toward food out of three chances
+        assert_eq!(predictions.food_rate("op"), 0.8);
+        assert_eq!(
+            predictions.most_likely_move("op"),
+            Some(Coord { x: 3, y: 7 })
+        );
+    }
+
+    #[test]
+    fn only_consecutive_turns_are_compared() {
+        let history = watch(&[0, 2, 4]);
+        assert!(!history.models.contains_key("op"));
+        assert_eq!(history.get_last_turn(), Some(4));
+
+        let history = watch(&[0, 1]);
+        assert!(!history.models.contains_key("me"));
+        assert!(history.models.contains_key("op"));
+    }
+
+    #[test]
+    fn predictions_are_a_distribution_over_safe_moves() {
+        let f = walk(1);
+        let b = f.board();
+        let predictions = watch(&[0, 1]).predict(&b, &f.you());
+
+        let moves = &predictions.moves["op"];
+        assert_eq!(moves.len(), 3);
+        let total: f32 = moves.iter().map(|(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+
+        // the neck isn't a move, so it carries no weight and no danger
+        let neck = Coord { x: 3, y: 3 };
+        assert_eq!(predictions.probability("op", &neck), Some(0.0));
+        assert_eq!(predictions.worst_case_weight("op", &neck), 0.0);
+
+        let up = Coord { x: 3, y: 5 };
+        assert_eq!(predictions.worst_case_weight("op", &up), 3.0);
+        assert!(predictions.weight("op", &up) > 1.0);
+
+        // nothing known about a snake means every square is equally likely
+        assert_eq!(predictions.weight("ghost", &up), 1.0);
+        assert_eq!(predictions.probability("ghost", &up), None);
+    }
+}
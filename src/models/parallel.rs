@@ -0,0 +1,66 @@
+use std::thread;
+
+// more threads than this stop paying for themselves on an 11x11 board
+const MAX_WORKERS: usize = 8;
+
+pub fn worker_count() -> usize {
+    return thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_WORKERS);
+}
+
+// `f` run once on each of `count` threads, passed its worker number, with
+// the results in worker order. the calling thread is worker 0, so one worker
+// runs inline without starting a thread
+pub fn run_workers<R, F>(count: usize, f: F) -> Vec<R>
+where
+    R: Send,
+    F: Fn(usize) -> R + Sync,
+{
+    if count <= 1 {
+        return vec![f(0)];
+    }
+
+    let f = &f;
+
+    return thread::scope(|scope| {
+        let handles: Vec<_> = (1..count).map(|i| scope.spawn(move || f(i))).collect();
+        let mut results = vec![f(0)];
+
+        // a panic in a worker carries on into the caller, same as running inline
+        for h in handles {
+            match h.join() {
+                Ok(r) => results.push(r),
+                Err(e) => std::panic::resume_unwind(e),
+            }
+        }
+
+        results
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_worker_runs_once_in_order() {
+        for count in [0, 1, 3, 8] {
+            let results = run_workers(count, |i| i * 10);
+            let expected: Vec<usize> = (0..count.max(1)).map(|i| i * 10).collect();
+            assert_eq!(results, expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "worker 2")]
+    fn a_worker_panic_reaches_the_caller() {
+        run_workers(3, |i| {
+            if i == 2 {
+                panic!("worker 2");
+            }
+            i
+        });
+    }
+}
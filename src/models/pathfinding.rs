@@ -0,0 +1,216 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use super::grid::Grid;
+use super::{Board, Coord, heuristic_from_n_to_end};
+
+const STEP_COST: u32 = 1;
+const SNAKE_COST: u32 = 1000;
+const NO_PARENT: u32 = u32::MAX;
+
+// A* over a flat grid. every buffer is sized once per board and reused, and
+// cells are reset lazily by stamping them with the current search number,
+// so a search only touches the cells it actually visits
+#[derive(Default)]
+pub struct PathFinder {
+    grid: Grid,
+    cost: Vec<u32>,
+    g_score: Vec<u32>,
+    came_from: Vec<u32>,
+    visited: Vec<u32>,
+    closed: Vec<u32>,
+    search: u32,
+    open: BinaryHeap<Reverse<(u32, u32)>>,
+    // what the costs were last built from, so every search on the same
+    // turn's board reuses them
+    loaded: Option<(u64, u16)>,
+}
+
+impl PathFinder {
+    pub fn new() -> PathFinder {
+        return PathFinder::default();
+    }
+
+    // entering a cell costs one step, plus a large penalty for every snake
+    // body on it. bodies are expensive rather than walls so a path through a
+    // tail that will have moved is still found
+    pub fn load(self: &mut Self, b: &Board) {
+        let key = (b.zobrist_hash(), b.hazard_damage);
+        if self.loaded == Some(key) {
+            return;
+        }
+        self.loaded = Some(key);
+
+        self.grid = Grid::new(b);
+        let cells = self.grid.cells();
+
+        if self.cost.len() != cells {
+            self.cost = vec![STEP_COST; cells];
+            self.g_score = vec![u32::MAX; cells];
+            self.came_from = vec![NO_PARENT; cells];
+            self.visited = vec![0; cells];
+            self.closed = vec![0; cells];
+            self.search = 0;
+        } else {
+            self.cost.fill(STEP_COST);
+        }
+
+        // hazards cost the health they'd take off us, so paths go around
+        // them when it's cheap to
+        for h in &b.hazards {
+            if let Some(i) = self.grid.index(h) {
+                self.cost[i] += b.hazard_damage as u32;
+            }
+        }
+
+        for snake in &b.snakes {
+            let mut seen: Vec<usize> = Vec::new();
+            for c in &snake.body {
+                if let Some(i) = self.grid.index(c) {
+                    // a stacked tail only counts once per snake
+                    if !seen.contains(&i) {
+                        seen.push(i);
+                        self.cost[i] += SNAKE_COST;
+                    }
+                }
+            }
+        }
+    }
+
+    fn heuristic(self: &Self, i: usize, end: Coord, b: &Board) -> u32 {
+        return heuristic_from_n_to_end(self.grid.coord(i), end, b) as u32;
+    }
+
+    // `b` has to be the board last passed to `load`
+    pub fn find(self: &mut Self, start: Coord, end: Coord, b: &Board) -> Option<Vec<Coord>> {
+        let start_i = self.grid.index(&start)?;
+        let end_i = self.grid.index(&end)?;
+
+        self.search = self.search.wrapping_add(1);
+        if self.search == 0 {
+            // stamps wrapped around, old marks could look current again
+            self.visited.fill(0);
+            self.closed.fill(0);
+            self.search = 1;
+        }
+        let search = self.search;
+
+        self.open.clear();
+        self.g_score[start_i] = 0;
+        self.came_from[start_i] = NO_PARENT;
+        self.visited[start_i] = search;
+        self.open
+            .push(Reverse((self.heuristic(start_i, end, b), start_i as u32)));
+
+        let mut nbs = [0usize; 4];
+
+        while let Some(Reverse((_, current))) = self.open.pop() {
+            let current = current as usize;
+
+            // stale heap entry for a cell we already expanded more cheaply
+            if self.closed[current] == search {
+                continue;
+            }
+            self.closed[current] = search;
+
+            if current == end_i {
+                return Some(self.reconstruct(current));
+            }
+
+            let n = self.grid.neighbours(current, &mut nbs);
+            for &next in &nbs[..n] {
+                if self.closed[next] == search {
+                    continue;
+                }
+
+                let tentative_g = self.g_score[current] + self.cost[next];
+
+                if self.visited[next] != search || tentative_g < self.g_score[next] {
+                    self.visited[next] = search;
+                    self.g_score[next] = tentative_g;
+                    self.came_from[next] = current as u32;
+
+                    let f = tentative_g + self.heuristic(next, end, b);
+                    self.open.push(Reverse((f, next as u32)));
+                }
+            }
+        }
+
+        return None;
+    }
+
+    fn reconstruct(self: &Self, mut current: usize) -> Vec<Coord> {
+        let mut path = vec![self.grid.coord(current)];
+
+        while self.came_from[current] != NO_PARENT {
+            current = self.came_from[current] as usize;
+            path.push(self.grid.coord(current));
+        }
+
+        path.reverse();
+        return path;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    fn path(pf: &mut PathFinder, b: &Board, from: (u16, u16), to: (u16, u16)) -> Vec<Coord> {
+        pf.load(b);
+        let start = Coord {
+            x: from.0,
+            y: from.1,
+        };
+        let end = Coord { x: to.0, y: to.1 };
+        return pf.find(start, end, b).unwrap();
+    }
+
+    #[test]
+    fn straight_line_on_an_empty_board() {
+        let b = Fixture::new(7, 7, vec![snake("me", &[(0, 0)], 90)]).board();
+        let mut pf = PathFinder::new();
+
+        let p = path(&mut pf, &b, (0, 0), (4, 0));
+        assert_eq!(p.len(), 5);
+        assert_eq!(p[0], Coord { x: 0, y: 0 });
+        assert_eq!(p[4], Coord { x: 4, y: 0 });
+    }
+
+    #[test]
+    fn goes_around_a_body_instead_of_through_it() {
+        let wall = [(2, 4), (2, 3), (2, 2), (2, 1), (2, 0)];
+        let b = Fixture::new(
+            7,
+            7,
+            vec![snake("me", &[(0, 0)], 90), snake("b", &wall, 90)],
+        )
+        .board();
+        let mut pf = PathFinder::new();
+
+        let p = path(&mut pf, &b, (0, 0), (4, 0));
+        assert!(p.iter().all(|c| !wall.contains(&(c.x as i32, c.y as i32))));
+        assert_eq!(p.len(), 15);
+    }
+
+    #[test]
+    fn costs_are_rebuilt_only_when_the_board_changes() {
+        let empty = Fixture::new(7, 7, vec![snake("me", &[(0, 0)], 90)]).board();
+        let wall = [(2, 4), (2, 3), (2, 2), (2, 1), (2, 0)];
+        let blocked = Fixture::new(
+            7,
+            7,
+            vec![snake("me", &[(0, 0)], 90), snake("b", &wall, 90)],
+        )
+        .board();
+        let mut pf = PathFinder::new();
+
+        assert_eq!(path(&mut pf, &empty, (0, 0), (4, 0)).len(), 5);
+        assert_eq!(pf.loaded.map(|k| k.0), Some(empty.zobrist_hash()));
+        assert_eq!(path(&mut pf, &empty, (0, 0), (4, 0)).len(), 5);
+
+        assert_eq!(path(&mut pf, &blocked, (0, 0), (4, 0)).len(), 15);
+        assert_eq!(path(&mut pf, &empty, (0, 0), (4, 0)).len(), 5);
+    }
+}
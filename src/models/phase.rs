@@ -0,0 +1,138 @@
+use super::grid::Grid;
+use super::{Board, ScoredMoves};
+
+// early turns, while everyone is short and spread out
+const OPENING_TURNS: u16 = 25;
+const OPENING_MAX_LENGTH: usize = 6;
+// under this share of the board empty, room matters more than anything
+const CROWDED_SPACE: f32 = 0.4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Opening,
+    MidGame,
+    Duel,
+    Endgame,
+}
+
+// multipliers on what each group of heuristics adds to a move's score
+#[derive(Copy, Clone, Debug)]
+pub struct PhaseWeights {
+    pub food: f32,
+    pub aggression: f32,
+    pub space: f32,
+    pub territory: f32,
+    pub chokepoints: f32,
+}
+
+impl Phase {
+    pub fn detect(b: &Board, turn: u16) -> Phase {
+        let cells = Grid::new(b).cells().max(1);
+        let occupied: usize = b.snakes.iter().map(|s| s.body.len()).sum();
+        let free = cells.saturating_sub(occupied) as f32 / cells as f32;
+
+        if free < CROWDED_SPACE {
+            return Phase::Endgame;
+        }
+
+        let short = b.snakes.iter().all(|s| s.body.len() <= OPENING_MAX_LENGTH);
+        if turn < OPENING_TURNS && short {
+            return Phase::Opening;
+        }
+
+        if b.snakes.len() <= 2 {
+            return Phase::Duel;
+        }
+
+        return Phase::MidGame;
+    }
+
+    // food wins the opening, space wins the end
+    pub fn get_weights(self: &Self) -> PhaseWeights {
+        return match self {
+            Phase::Opening => PhaseWeights {
+                food: 1.5,
+                aggression: 0.5,
+                space: 0.5,
+                territory: 0.5,
+                chokepoints: 1.0,
+            },
+            // a duel is played by the search, so these only weigh the
+            // heuristics when it couldn't decide
+            Phase::MidGame | Phase::Duel => PhaseWeights {
+                food: 1.0,
+                aggression: 1.0,
+                space: 1.0,
+                territory: 1.0,
+                chokepoints: 1.0,
+            },
+            Phase::Endgame => PhaseWeights {
+                food: 0.5,
+                aggression: 1.0,
+                space: 1.5,
+                territory: 1.5,
+                chokepoints: 1.5,
+            },
+        };
+    }
+}
+
+impl ScoredMoves {
+    // run a heuristic and scale whatever it adds to or takes off each move
+    pub fn apply_weighted(self: &mut Self, weight: f32, heuristic: impl FnOnce(&mut ScoredMoves)) {
+        let before = self.clone();
+        heuristic(self);
+
+        for (now, was) in [
+            (&mut self.Left, before.Left),
+            (&mut self.Right, before.Right),
+            (&mut self.Down, before.Down),
+            (&mut self.Up, before.Up),
+        ] {
+            let delta = (now.score as i32 - was.score as i32) as f32 * weight;
+            now.score = was.score.saturating_add(delta.round() as i16);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    // snakes of the given lengths, each stacked on its own cell of a 10x10
+    // board so the numbers of occupied cells are easy to count
+    fn detect(turn: u16, lengths: &[usize]) -> Phase {
+        let snakes = lengths
+            .iter()
+            .enumerate()
+            .map(|(i, len)| snake(&format!("s{}", i), &vec![(i as i32, 0); *len], 90))
+            .collect();
+        let b = Fixture::new(10, 10, snakes).with_turn(turn).board();
+
+        return Phase::detect(&b, turn);
+    }
+
+    #[test]
+    fn the_opening_lasts_while_everyone_is_short() {
+        assert_eq!(detect(OPENING_TURNS - 1, &[6, 6, 6]), Phase::Opening);
+        assert_eq!(detect(OPENING_TURNS, &[6, 6, 6]), Phase::MidGame);
+        assert_eq!(detect(OPENING_TURNS - 1, &[6, 7, 6]), Phase::MidGame);
+        assert_eq!(detect(0, &[3, 3]), Phase::Opening);
+    }
+
+    #[test]
+    fn two_snakes_left_is_a_duel() {
+        assert_eq!(detect(OPENING_TURNS, &[8, 8]), Phase::Duel);
+        assert_eq!(detect(OPENING_TURNS, &[8, 8, 8]), Phase::MidGame);
+        assert_eq!(detect(OPENING_TURNS, &[8]), Phase::Duel);
+    }
+
+    #[test]
+    fn a_crowded_board_is_the_endgame_from_any_turn() {
+        // exactly 40% of the hundred cells free is not yet crowded
+        assert_eq!(detect(OPENING_TURNS, &[30, 30]), Phase::Duel);
+        assert_eq!(detect(OPENING_TURNS, &[30, 31]), Phase::Endgame);
+        assert_eq!(detect(0, &[20, 20, 21]), Phase::Endgame);
+    }
+}
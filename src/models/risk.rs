@@ -0,0 +1,121 @@
+const RISK_VAR: &str = "BATTLESNAKE_RISK";
+
+// the decisions made without knowing what an opponent will do
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    // going for food a snake at least our size can reach with us
+    Food,
+    // moving next to the head of a snake at least our size
+    Heads,
+    // picking our side of a head to head
+    Nash,
+}
+
+// how each decision with an uncertain outcome is weighed. 0 plans for the
+// worst thing an opponent could do, 1 for what they're likely to do on
+// average, anything between is a blend of the two
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RiskProfile {
+    food: f32,
+    heads: f32,
+    nash: f32,
+}
+
+// how the snake played before it could be set: contested food is left
+// alone, larger heads count as often as they're predicted to move next to
+// us, and head to heads are played for the worst case
+impl Default for RiskProfile {
+    fn default() -> RiskProfile {
+        return RiskProfile {
+            food: 0.0,
+            heads: 1.0,
+            nash: 0.0,
+        };
+    }
+}
+
+impl RiskProfile {
+    // "worst", "expected", "blend" or a boldness between 0 and 1, used for
+    // every decision
+    pub fn parse(s: &str) -> Option<RiskProfile> {
+        let boldness = match s.trim() {
+            "worst" => 0.0,
+            "expected" => 1.0,
+            "blend" => 0.5,
+            n => n.parse::<f32>().ok().filter(|b| (0.0..=1.0).contains(b))?,
+        };
+
+        return Some(RiskProfile {
+            food: boldness,
+            heads: boldness,
+            nash: boldness,
+        });
+    }
+
+    // set per deployment, so one build can run a bold snake on one ladder
+    // and a careful one on another
+    pub fn from_env() -> RiskProfile {
+        let value = match std::env::var(RISK_VAR) {
+            Ok(v) => v,
+            Err(_) => return RiskProfile::default(),
+        };
+
+        return match RiskProfile::parse(&value) {
+            Some(r) => r,
+            None => {
+                eprintln!("[error] ignoring {}={}, using the default", RISK_VAR, value);
+                RiskProfile::default()
+            }
+        };
+    }
+
+    pub fn get_boldness(self: &Self, decision: Decision) -> f32 {
+        return match decision {
+            Decision::Food => self.food,
+            Decision::Heads => self.heads,
+            Decision::Nash => self.nash,
+        };
+    }
+
+    pub fn blend(self: &Self, decision: Decision, worst: f32, expected: f32) -> f32 {
+        return worst + (expected - worst) * self.get_boldness(decision);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_numbers_set_every_decision() {
+        let cases = [
+            ("worst", 0.0),
+            ("expected", 1.0),
+            ("blend", 0.5),
+            (" 0.25\n", 0.25),
+            ("1", 1.0),
+        ];
+
+        for (s, boldness) in cases {
+            let r = RiskProfile::parse(s).unwrap();
+            for d in [Decision::Food, Decision::Heads, Decision::Nash] {
+                assert_eq!(r.get_boldness(d), boldness, "{:?} for {:?}", d, s);
+            }
+        }
+    }
+
+    #[test]
+    fn anything_else_is_rejected() {
+        for s in ["", "bold", "-0.1", "1.5", "NaN", "0,5"] {
+            assert_eq!(RiskProfile::parse(s), None, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn the_default_keeps_the_old_decisions() {
+        let r = RiskProfile::default();
+        assert_eq!(r.blend(Decision::Food, 0.0, 0.7), 0.0);
+        assert_eq!(r.blend(Decision::Heads, 4.0, 1.5), 1.5);
+        assert_eq!(r.blend(Decision::Nash, 0.3, 1.0), 0.3);
+    }
+}
@@ -0,0 +1,823 @@
+use std::collections::VecDeque;
+
+use super::grid::Grid;
+use super::zobrist;
+use super::{Battlesnake, Board, Direction, ScoredMoves};
+
+// only opponents this close can reach a cell next to our head this turn
+const THREAT_RANGE: u16 = 2;
+// more than this many joint replies aren't worth enumerating for one move
+const MAX_THREATS: usize = 3;
+
+// body segments as cell indices, head first
+#[derive(Clone, Debug, PartialEq)]
+struct Snake {
+    body: VecDeque<u16>,
+    health: u8,
+    alive: bool,
+}
+
+// what one snake's move changed, so it can be put back
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct SnakeUndo {
+    slot: u8,
+    health: u8,
+    tail: Option<u16>,
+    moved: bool,
+    grew: bool,
+    died: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Frame {
+    hash: u64,
+    undo_start: usize,
+    eaten_start: usize,
+}
+
+// buffers for walking the board from a position, cleared before each use
+#[derive(Clone, Debug)]
+struct Scratch {
+    seen: Vec<bool>,
+    stack: Vec<usize>,
+    owner: Vec<u8>,
+    reached: Vec<u16>,
+    queue: VecDeque<(usize, u16)>,
+}
+
+impl Scratch {
+    fn new(cells: usize) -> Scratch {
+        return Scratch {
+            seen: vec![false; cells],
+            stack: Vec::with_capacity(cells),
+            owner: vec![u8::MAX; cells],
+            reached: vec![u16::MAX; cells],
+            queue: VecDeque::with_capacity(cells),
+        };
+    }
+}
+
+// what's left in them says nothing about the position
+impl PartialEq for Scratch {
+    fn eq(self: &Self, _: &Self) -> bool {
+        return true;
+    }
+}
+
+// the position without any of the payload's strings, for simulating moves.
+// snakes keep their slot from the board for the whole search, dead ones
+// stay in place and are skipped. every buffer is reused between moves so a
+// search can make and unmake without allocating once it has warmed up
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameState {
+    grid: Grid,
+    snakes: Vec<Snake>,
+    food: Vec<bool>,
+    hazards: Vec<u8>,
+    hazard_damage: u16,
+    // body segments on each cell, a stacked tail counts twice
+    occupancy: Vec<u8>,
+    hash: u64,
+    undo: Vec<SnakeUndo>,
+    eaten: Vec<u16>,
+    frames: Vec<Frame>,
+    scratch: Scratch,
+}
+
+impl GameState {
+    pub fn new(b: &Board) -> GameState {
+        let grid = Grid::new(b);
+        let cells = grid.cells();
+
+        let mut food = vec![false; cells];
+        for f in &b.food {
+            if let Some(i) = grid.index(f) {
+                food[i] = true;
+            }
+        }
+
+        let mut hazards = vec![0u8; cells];
+        for h in &b.hazards {
+            if let Some(i) = grid.index(h) {
+                hazards[i] = hazards[i].saturating_add(1);
+            }
+        }
+
+        let mut occupancy = vec![0u8; cells];
+        let mut snakes: Vec<Snake> = Vec::with_capacity(b.snakes.len());
+
+        for s in &b.snakes {
+            let body: VecDeque<u16> = s
+                .body
+                .iter()
+                .filter_map(|c| grid.index(c))
+                .map(|i| i as u16)
+                .collect();
+
+            for c in &body {
+                occupancy[*c as usize] += 1;
+            }
+
+            snakes.push(Snake {
+                alive: !body.is_empty() && s.health > 0,
+                body,
+                health: s.health.min(u8::MAX as u16) as u8,
+            });
+        }
+
+        let mut state = GameState {
+            grid,
+            snakes,
+            food,
+            hazards,
+            hazard_damage: b.hazard_damage,
+            occupancy,
+            hash: 0,
+            undo: Vec::new(),
+            eaten: Vec::new(),
+            frames: Vec::new(),
+            scratch: Scratch::new(cells),
+        };
+
+        state.hash = state.compute_hash();
+        return state;
+    }
+
+    pub fn is_alive(self: &Self, slot: usize) -> bool {
+        return self.snakes.get(slot).is_some_and(|s| s.alive);
+    }
+
+    fn head(self: &Self, slot: usize) -> Option<usize> {
+        return self.snakes[slot].body.front().map(|c| *c as usize);
+    }
+
+    pub fn get_hash(self: &Self) -> u64 {
+        return self.hash;
+    }
+
+    // whether `victim` died this turn by putting its head on `killer`,
+    // either head to head or into its body
+    pub fn is_killed_by(self: &Self, victim: usize, killer: usize) -> bool {
+        if victim == killer || self.is_alive(victim) {
+            return false;
+        }
+
+        // only a death in the last make_moves, with a head to judge it by
+        let start = match self.frames.last() {
+            Some(f) => f.undo_start,
+            None => return false,
+        };
+        let moved = self.undo[start..]
+            .iter()
+            .any(|u| u.slot as usize == victim && u.moved && u.died);
+
+        return match self.head(victim) {
+            Some(h) if moved => self.snakes[killer].body.contains(&(h as u16)),
+            _ => false,
+        };
+    }
+
+    pub fn get_health(self: &Self, slot: usize) -> u8 {
+        return self.snakes[slot].health;
+    }
+
+    pub fn get_length(self: &Self, slot: usize) -> usize {
+        return self.snakes[slot].body.len();
+    }
+
+    // empty cells this snake's head can walk to, stopping once `cap` are
+    // found. bodies count as walls however soon they'll move
+    pub fn count_space(self: &mut Self, slot: usize, cap: usize) -> usize {
+        let head = match self.head(slot) {
+            Some(h) if self.snakes[slot].alive => h,
+            _ => return 0,
+        };
+
+        let Scratch { seen, stack, .. } = &mut self.scratch;
+        seen.fill(false);
+        stack.clear();
+        stack.push(head);
+        let mut nbs = [0usize; 4];
+        let mut count = 0;
+        seen[head] = true;
+
+        while let Some(cell) = stack.pop() {
+            let n = self.grid.neighbours(cell, &mut nbs);
+            for &next in &nbs[..n] {
+                if seen[next] || self.occupancy[next] > 0 {
+                    continue;
+                }
+
+                seen[next] = true;
+                count += 1;
+                if count >= cap {
+                    return count;
+                }
+                stack.push(next);
+            }
+        }
+
+        return count;
+    }
+
+    // empty cells each of two snakes reaches strictly first, a tie goes to
+    // the longer one or to nobody
+    pub fn count_territory(self: &mut Self, a: usize, b: usize) -> (usize, usize) {
+        let heads = [a, b].map(|slot| self.head(slot).filter(|_| self.snakes[slot].alive));
+        let longer = match self.get_length(a).cmp(&self.get_length(b)) {
+            std::cmp::Ordering::Greater => 0,
+            std::cmp::Ordering::Less => 1,
+            std::cmp::Ordering::Equal => 2,
+        };
+
+        // which snake got to each cell, 2 when it's a tie, and when
+        let Scratch {
+            owner,
+            reached,
+            queue: q,
+            ..
+        } = &mut self.scratch;
+        owner.fill(u8::MAX);
+        reached.fill(u16::MAX);
+        q.clear();
+        let mut nbs = [0usize; 4];
+
+        for (side, head) in heads.iter().enumerate() {
+            if let Some(h) = *head {
+                q.push_back((h, 0));
+                owner[h] = side as u8;
+                reached[h] = 0;
+            }
+        }
+
+        while let Some((cell, d)) = q.pop_front() {
+            let side = owner[cell];
+            if side == 2 {
+                continue;
+            }
+
+            let n = self.grid.neighbours(cell, &mut nbs);
+            for &next in &nbs[..n] {
+                if self.occupancy[next] > 0 {
+                    continue;
+                }
+
+                if reached[next] == u16::MAX {
+                    reached[next] = d + 1;
+                    owner[next] = side;
+                    q.push_back((next, d + 1));
+                } else if reached[next] == d + 1 && owner[next] != side && owner[next] != 2 {
+                    owner[next] = longer;
+                }
+            }
+        }
+
+        let mut counts = (0, 0);
+        for (o, occupied) in owner.iter().zip(&self.occupancy) {
+            if *occupied > 0 {
+                continue;
+            }
+            match o {
+                0 => counts.0 += 1,
+                1 => counts.1 += 1,
+                _ => {}
+            }
+        }
+
+        return counts;
+    }
+
+    // moves that stay on the board and don't turn back into the neck. a snake
+    // with no legal move still has to make one, and dies of it
+    pub fn legal_moves(self: &Self, slot: usize, out: &mut [Direction; 4]) -> usize {
+        let snake = &self.snakes[slot];
+        let head = match self.head(slot) {
+            Some(h) => h,
+            None => return 0,
+        };
+        let neck = snake.body.get(1).map(|c| *c as usize);
+
+        let mut n = 0;
+        for dir in Direction::ALL {
+            match self.grid.step(head, dir) {
+                Some(next) if Some(next) != neck || neck == Some(head) => {
+                    out[n] = dir;
+                    n += 1;
+                }
+                _ => {}
+            }
+        }
+
+        return n;
+    }
+
+    // the parts of the hash that belong to one snake
+    fn snake_hash(self: &Self, slot: usize) -> u64 {
+        let snake = &self.snakes[slot];
+        let body = snake.body.iter().map(|c| *c as usize);
+
+        return zobrist::body_key(slot, body) ^ zobrist::health_key(slot, snake.health);
+    }
+
+    // the same hash Board::zobrist_hash gives the board this came from, as
+    // long as every snake on it is alive
+    fn compute_hash(self: &Self) -> u64 {
+        let mut h = zobrist::board_key(&self.grid);
+
+        for slot in 0..self.snakes.len() {
+            if self.snakes[slot].alive {
+                h ^= self.snake_hash(slot);
+            }
+        }
+
+        for cell in 0..self.grid.cells() {
+            if self.food[cell] {
+                h ^= zobrist::food_key(cell);
+            }
+
+            if self.hazards[cell] % 2 == 1 {
+                h ^= zobrist::hazard_key(cell);
+            }
+        }
+
+        return h;
+    }
+
+    // one turn of the standard rules: every live snake moves (`moves` is
+    // indexed by slot), loses health and hazard damage, eats, and then
+    // starvation, walls, bodies and head to heads are all checked at once
+    pub fn make_moves(self: &mut Self, moves: &[Direction]) {
+        let undo_start = self.undo.len();
+        self.frames.push(Frame {
+            hash: self.hash,
+            undo_start,
+            eaten_start: self.eaten.len(),
+        });
+
+        for slot in 0..self.snakes.len() {
+            if !self.snakes[slot].alive {
+                continue;
+            }
+
+            // every segment moves one place along from the tail, so the
+            // snake is rehashed whole rather than a segment at a time
+            self.hash ^= self.snake_hash(slot);
+
+            let next = self.head(slot).and_then(|h| {
+                self.grid
+                    .step(h, moves.get(slot).copied().unwrap_or(Direction::Up))
+            });
+            let snake = &mut self.snakes[slot];
+
+            let tail = snake.body.pop_back();
+            if let Some(t) = tail {
+                self.occupancy[t as usize] -= 1;
+            }
+
+            // off the board: the body still follows for everyone else's
+            // collisions this turn, but there is no new head
+            if let Some(n) = next {
+                snake.body.push_front(n as u16);
+                self.occupancy[n] += 1;
+            }
+
+            let mut health = snake.health.saturating_sub(1);
+            let mut grew = false;
+
+            if let Some(n) = next {
+                let damage = self.hazards[n] as u16 * self.hazard_damage;
+                health = (health as u16).saturating_sub(damage) as u8;
+
+                if self.food[n] {
+                    health = 100;
+                    grew = true;
+
+                    if let Some(t) = snake.body.back().copied() {
+                        snake.body.push_back(t);
+                        self.occupancy[t as usize] += 1;
+                    }
+
+                    if !self.eaten.contains(&(n as u16)) {
+                        self.eaten.push(n as u16);
+                    }
+                }
+            }
+
+            self.undo.push(SnakeUndo {
+                slot: slot as u8,
+                health: snake.health,
+                tail,
+                moved: next.is_some(),
+                grew,
+                died: false,
+            });
+
+            snake.health = health;
+            self.hash ^= self.snake_hash(slot);
+        }
+
+        // two snakes eating the same food share it
+        for i in self.undo_eaten_start()..self.eaten.len() {
+            let cell = self.eaten[i] as usize;
+            self.food[cell] = false;
+            self.hash ^= zobrist::food_key(cell);
+        }
+
+        // decide every elimination before removing anyone, their bodies
+        // still count for everyone else's collisions this turn
+        for u in undo_start..self.undo.len() {
+            let slot = self.undo[u].slot as usize;
+            if self.is_eliminated(slot, self.undo[u].moved) {
+                self.undo[u].died = true;
+            }
+        }
+
+        for u in undo_start..self.undo.len() {
+            if !self.undo[u].died {
+                continue;
+            }
+
+            let slot = self.undo[u].slot as usize;
+            self.hash ^= self.snake_hash(slot);
+            self.snakes[slot].alive = false;
+
+            for c in &self.snakes[slot].body {
+                self.occupancy[*c as usize] -= 1;
+            }
+        }
+
+        debug_assert_eq!(self.hash, self.compute_hash());
+    }
+
+    fn undo_eaten_start(self: &Self) -> usize {
+        return self.frames.last().map_or(0, |f| f.eaten_start);
+    }
+
+    fn is_eliminated(self: &Self, slot: usize, moved: bool) -> bool {
+        let snake = &self.snakes[slot];
+
+        if !moved || snake.health == 0 {
+            return true;
+        }
+
+        let head = match self.head(slot) {
+            Some(h) => h,
+            None => return true,
+        };
+
+        let mut heads_here = 0;
+        for (other, s) in self.snakes.iter().enumerate() {
+            if !s.alive || self.head(other) != Some(head) {
+                continue;
+            }
+
+            heads_here += 1;
+
+            if other != slot && s.body.len() >= snake.body.len() {
+                return true;
+            }
+        }
+
+        // anything on the cell that isn't a head is a body we ran into
+        return self.occupancy[head] > heads_here;
+    }
+
+    // put back the last make_moves
+    pub fn unmake(self: &mut Self) {
+        let frame = match self.frames.pop() {
+            Some(f) => f,
+            None => return,
+        };
+
+        while self.undo.len() > frame.undo_start {
+            let u = match self.undo.pop() {
+                Some(u) => u,
+                None => break,
+            };
+            let snake = &mut self.snakes[u.slot as usize];
+
+            if u.died {
+                snake.alive = true;
+                for c in &snake.body {
+                    self.occupancy[*c as usize] += 1;
+                }
+            }
+
+            if u.grew
+                && let Some(t) = snake.body.pop_back()
+            {
+                self.occupancy[t as usize] -= 1;
+            }
+
+            if u.moved
+                && let Some(h) = snake.body.pop_front()
+            {
+                self.occupancy[h as usize] -= 1;
+            }
+
+            if let Some(t) = u.tail {
+                snake.body.push_back(t);
+                self.occupancy[t as usize] += 1;
+            }
+
+            snake.health = u.health;
+        }
+
+        for cell in self.eaten.drain(frame.eaten_start..) {
+            self.food[cell as usize] = true;
+        }
+
+        self.hash = frame.hash;
+    }
+}
+
+impl Battlesnake {
+    // play out every reply the nearby opponents could make to each of our
+    // moves under the real rules. a move we die after whatever they do is
+    // as bad as a wall, even when each heuristic on its own thinks it's fine.
+    // `state` is `b`'s, and is left as it was found
+    pub fn avoid_certain_death(
+        self: &Self,
+        sm: &mut ScoredMoves,
+        b: &Board,
+        state: &mut GameState,
+    ) {
+        let me = match b.snakes.iter().position(|s| s.id == self.id) {
+            Some(i) => i,
+            None => return,
+        };
+
+        let mut out = [Direction::Up; 4];
+
+        // nothing to simulate without a body to move
+        if !state.is_alive(me) {
+            return;
+        }
+
+        // far away snakes can't touch us this turn, any legal move will do
+        let mut moves: Vec<Direction> = (0..b.snakes.len())
+            .map(|s| match state.legal_moves(s, &mut out) {
+                0 => Direction::Up,
+                _ => out[0],
+            })
+            .collect();
+
+        let mut threats: Vec<(usize, Vec<Direction>)> = b
+            .snakes
+            .iter()
+            .enumerate()
+            .filter(|(s, snake)| {
+                *s != me && snake.head.get_distance_on(&self.head, b) <= THREAT_RANGE
+            })
+            .map(|(s, _)| {
+                let n = state.legal_moves(s, &mut out);
+                (s, out[..n].to_vec())
+            })
+            .filter(|(_, m)| !m.is_empty())
+            .collect();
+
+        threats.sort_by_key(|(s, _)| b.snakes[*s].head.get_distance_on(&self.head, b));
+        threats.truncate(MAX_THREATS);
+
+        for dir in Direction::ALL {
+            if sm.is_dead(dir) {
+                continue;
+            }
+
+            moves[me] = dir;
+
+            // odometer over every combination of the threats' moves
+            let mut picks = vec![0usize; threats.len()];
+
+            let survived = loop {
+                for (t, (s, options)) in threats.iter().enumerate() {
+                    moves[*s] = options[picks[t]];
+                }
+
+                state.make_moves(&moves);
+                let alive = state.is_alive(me);
+                state.unmake();
+
+                if alive {
+                    break true;
+                }
+
+                let mut t = 0;
+                while t < picks.len() {
+                    picks[t] += 1;
+                    if picks[t] < threats[t].1.len() {
+                        break;
+                    }
+                    picks[t] = 0;
+                    t += 1;
+                }
+
+                if t == picks.len() {
+                    break false;
+                }
+            };
+
+            if !survived {
+                sm.kill(dir);
+                println!("[doomed {}]: no reply lets us live", dir.get_str());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    use Direction::{Down, Left, Right, Up};
+
+    // make every turn in `turns`, checking the incremental hash against a
+    // full rehash after each one, then unmake them all again
+    fn play(state: &mut GameState, turns: &[[Direction; 2]]) {
+        let mut hashes = vec![state.get_hash()];
+
+        for moves in turns {
+            state.make_moves(moves);
+            assert_eq!(state.get_hash(), state.compute_hash());
+            hashes.push(state.get_hash());
+        }
+
+        for _ in turns {
+            hashes.pop();
+            state.unmake();
+            assert_eq!(state.get_hash(), state.compute_hash());
+            assert_eq!(Some(&state.get_hash()), hashes.last());
+        }
+    }
+
+    #[test]
+    fn hash_matches_the_board() {
+        let f = Fixture::new(
+            7,
+            7,
+            vec![
+                snake("me", &[(1, 1), (1, 1), (1, 1)], 100),
+                snake("b", &[(5, 5), (5, 4), (4, 4), (4, 4)], 60),
+            ],
+        )
+        .with_food(&[(1, 2), (3, 3)])
+        .with_hazards(&[(0, 0)]);
+        let b = f.board();
+
+        assert_eq!(GameState::new(&b).get_hash(), b.zobrist_hash());
+    }
+
+    #[test]
+    fn incremental_hash_through_stacked_segments() {
+        // both snakes start stacked on one cell and eat early, so stacked
+        // tails are made and unmade on the way
+        let f = Fixture::new(
+            7,
+            7,
+            vec![
+                snake("me", &[(1, 1), (1, 1), (1, 1)], 100),
+                snake("b", &[(5, 5), (5, 5), (5, 5)], 100),
+            ],
+        )
+        .with_food(&[(1, 2), (4, 5), (1, 4)]);
+        let mut state = GameState::new(&f.board());
+
+        play(
+            &mut state,
+            &[
+                [Up, Left],
+                [Up, Down],
+                [Up, Left],
+                [Right, Down],
+                [Down, Right],
+                [Down, Up],
+            ],
+        );
+    }
+
+    // make one turn, check who survived it, and check unmake puts back
+    // every field and the hash exactly
+    fn make_and_restore(f: Fixture, moves: [Direction; 2], alive: [bool; 2]) -> GameState {
+        let mut state = GameState::new(&f.board());
+        let before = state.clone();
+
+        state.make_moves(&moves);
+        assert_eq!([state.is_alive(0), state.is_alive(1)], alive);
+        assert_eq!(state.get_hash(), state.compute_hash());
+        let after = state.clone();
+
+        state.unmake();
+        assert_eq!(state, before);
+        return after;
+    }
+
+    #[test]
+    fn unmake_restores_eating() {
+        let f = Fixture::new(
+            7,
+            7,
+            vec![
+                snake("me", &[(1, 1), (1, 0), (0, 0)], 50),
+                snake("b", &[(5, 5), (5, 4), (5, 3)], 50),
+            ],
+        )
+        .with_food(&[(1, 2), (6, 6)]);
+
+        let after = make_and_restore(f, [Up, Up], [true, true]);
+        assert_eq!(after.get_length(0), 4);
+        assert_eq!(after.get_health(0), 100);
+        assert_eq!(after.get_health(1), 49);
+        assert_eq!(after.food.iter().filter(|f| **f).count(), 1);
+    }
+
+    #[test]
+    fn unmake_restores_a_body_collision() {
+        let f = Fixture::new(
+            7,
+            7,
+            vec![
+                snake("me", &[(2, 2), (1, 2), (0, 2)], 50),
+                snake("b", &[(3, 4), (3, 3), (3, 2), (3, 1)], 50),
+            ],
+        );
+
+        let after = make_and_restore(f, [Right, Up], [false, true]);
+        assert_eq!(
+            after.occupancy.iter().map(|o| *o as usize).sum::<usize>(),
+            4
+        );
+    }
+
+    #[test]
+    fn unmake_restores_head_to_heads() {
+        let me = snake("me", &[(1, 1), (0, 1), (0, 0)], 50);
+
+        let longer = Fixture::new(
+            7,
+            7,
+            vec![
+                me.clone(),
+                snake("b", &[(3, 1), (4, 1), (5, 1), (6, 1)], 50),
+            ],
+        );
+        make_and_restore(longer, [Right, Left], [false, true]);
+
+        let equal = Fixture::new(7, 7, vec![me, snake("b", &[(3, 1), (4, 1), (5, 1)], 50)]);
+        let after = make_and_restore(equal, [Right, Left], [false, false]);
+        assert!(after.occupancy.iter().all(|o| *o == 0));
+    }
+
+    #[test]
+    fn a_doomed_move_stays_dead_under_later_heuristics() {
+        // stepping into the hazard takes our last health
+        let f = Fixture::new(
+            11,
+            11,
+            vec![
+                snake("me", &[(3, 3), (2, 3), (1, 3)], 3),
+                snake("b", &[(9, 9), (9, 8), (9, 7)], 90),
+            ],
+        )
+        .with_hazards(&[(4, 3)]);
+        let b = f.board();
+        let me = f.you();
+        let mut state = GameState::new(&b);
+
+        let mut sm = ScoredMoves::init();
+        me.avoid_own_neck(&mut sm, &b);
+        me.avoid_certain_death(&mut sm, &b, &mut state);
+        assert!(sm.is_dead(Right));
+
+        me.use_flood_fill(&mut sm, &b);
+        assert!(sm.get(Right).score > ScoredMoves::DEATH);
+        assert!(sm.is_dead(Right));
+        assert_eq!(sm.get_alive_moves(), vec![Down, Up]);
+        assert_ne!(me.choose_move(&sm).direction, Right);
+    }
+
+    #[test]
+    fn counts_come_out_the_same_from_reused_buffers() {
+        let f = Fixture::new(
+            7,
+            7,
+            vec![
+                snake("me", &[(1, 1), (1, 0), (0, 0)], 50),
+                snake("b", &[(5, 5), (5, 4), (5, 3)], 50),
+            ],
+        );
+        let mut state = GameState::new(&f.board());
+
+        let territory = state.count_territory(0, 1);
+        let space = state.count_space(0, 100);
+        assert_eq!(space, 49 - 6);
+
+        state.make_moves(&[Up, Up]);
+        state.count_territory(1, 0);
+        state.count_space(1, 5);
+        state.unmake();
+
+        assert_eq!(state.count_territory(0, 1), territory);
+        assert_eq!(state.count_space(0, 100), space);
+    }
+}
@@ -0,0 +1,385 @@
+use std::time::{Duration, Instant};
+
+use super::distance::vacate_times;
+use super::grid::Grid;
+use super::{Battlesnake, Board, Direction, ScoredMoves, flood_fill};
+
+// a path this long counts as having got out
+pub const MAX_DEPTH: u16 = 128;
+const NODE_BUDGET: u32 = 200_000;
+const SURVIVAL_BUDGET: Duration = Duration::from_millis(100);
+const SURVIVAL_WEIGHT: i16 = 1500;
+
+// depth first search for the longest walk a snake can make before running
+// out of cells or health. a cell is usable once whatever was on it has moved
+// off, and a cell the snake walks over stays blocked until its own tail
+// passes it. every food eaten on the way refills health and holds the
+// snake's own cells one turn longer
+struct LongestPath {
+    grid: Grid,
+    free_at: Vec<u16>,
+    // cells of the walking snake's starting body, which stall when it grows
+    own: Vec<bool>,
+    // when the walk entered each cell plus its starting length, 0 if it hasn't
+    occupied_until: Vec<u16>,
+    food: Vec<bool>,
+    damage: Vec<u16>,
+    length: u16,
+    grown: u16,
+    nodes: u32,
+    started: Instant,
+    budget: Duration,
+    cut_short: bool,
+}
+
+impl LongestPath {
+    fn new(b: &Board, snake: &Battlesnake, budget: Duration) -> LongestPath {
+        let grid = Grid::new(b);
+        let cells = grid.cells();
+
+        let mut own = vec![false; cells];
+        for c in &snake.body {
+            if let Some(i) = grid.index(c) {
+                own[i] = true;
+            }
+        }
+
+        let mut food = vec![false; cells];
+        for f in &b.food {
+            if let Some(i) = grid.index(f) {
+                food[i] = true;
+            }
+        }
+
+        let mut damage = vec![1u16; cells];
+        for h in &b.hazards {
+            if let Some(i) = grid.index(h) {
+                damage[i] = damage[i].saturating_add(b.hazard_damage);
+            }
+        }
+
+        let length = snake.body.len().min(u16::MAX as usize) as u16;
+        let mut occupied_until = vec![0; cells];
+
+        // the current head is still under the body for the whole walk
+        if let Some(h) = grid.index(&snake.head) {
+            occupied_until[h] = length;
+        }
+
+        return LongestPath {
+            grid,
+            free_at: vacate_times(b, &grid),
+            own,
+            occupied_until,
+            food,
+            damage,
+            length,
+            grown: 0,
+            nodes: 0,
+            started: Instant::now(),
+            budget,
+            cut_short: false,
+        };
+    }
+
+    fn out_of_budget(self: &mut Self) -> bool {
+        if self.nodes >= NODE_BUDGET
+            || (self.nodes.is_multiple_of(1024) && self.started.elapsed() >= self.budget)
+        {
+            self.cut_short = true;
+        }
+        return self.cut_short;
+    }
+
+    fn is_open(self: &Self, cell: usize, t: u16) -> bool {
+        let free_at = match self.own[cell] {
+            true => self.free_at[cell] + self.grown,
+            false => self.free_at[cell],
+        };
+
+        let ours = self.occupied_until[cell];
+        return free_at <= t && (ours == 0 || ours + self.grown <= t);
+    }
+
+    // health left after stepping onto `cell`, eating happens before starving
+    fn health_after(self: &Self, cell: usize, health: u16) -> Option<u16> {
+        if self.food[cell] {
+            return Some(100);
+        }
+
+        return match health.saturating_sub(self.damage[cell]) {
+            0 => None,
+            h => Some(h),
+        };
+    }
+
+    // longest number of further moves possible after stepping onto `cell`
+    // at time `t`
+    fn search(self: &mut Self, cell: usize, t: u16, health: u16) -> u16 {
+        self.nodes += 1;
+
+        if t >= MAX_DEPTH || self.out_of_budget() {
+            return 0;
+        }
+
+        let ate = self.food[cell];
+        if ate {
+            self.food[cell] = false;
+            self.grown += 1;
+        }
+
+        let previous = self.occupied_until[cell];
+        self.occupied_until[cell] = t + self.length;
+
+        let mut nbs = [0usize; 4];
+        let n = self.grid.neighbours(cell, &mut nbs);
+        let mut best = 0;
+
+        for &next in &nbs[..n] {
+            if !self.is_open(next, t + 1) {
+                continue;
+            }
+
+            let h = match self.health_after(next, health) {
+                Some(h) => h,
+                None => continue,
+            };
+
+            let depth = 1 + self.search(next, t + 1, h);
+            if depth > best {
+                best = depth;
+            }
+
+            if t + best >= MAX_DEPTH {
+                break;
+            }
+        }
+
+        self.occupied_until[cell] = previous;
+        if ate {
+            self.food[cell] = true;
+            self.grown -= 1;
+        }
+
+        return best;
+    }
+}
+
+// how long a snake can keep going, starting with `first`. `exact` is false
+// when the search ran out of budget and `turns` is only a lower bound
+#[derive(Copy, Clone, Debug)]
+pub struct Survival {
+    first: Direction,
+    turns: u16,
+    exact: bool,
+}
+
+impl Survival {
+    pub fn get_first(self: &Self) -> Direction {
+        return self.first;
+    }
+
+    pub fn get_turns(self: &Self) -> u16 {
+        return self.turns;
+    }
+
+    pub fn is_exact(self: &Self) -> bool {
+        return self.exact;
+    }
+}
+
+impl Battlesnake {
+    // every move we can make leads somewhere smaller than our body
+    pub fn is_trapped(self: &Self, sm: &ScoredMoves, b: &Board) -> bool {
+        let mut any_move = false;
+
+        for dir in Direction::ALL {
+            if sm.is_dead(dir) {
+                continue;
+            }
+
+            if let Some(next) = self.head.get_next_coord(dir, b) {
+                any_move = true;
+                if flood_fill(b, next) >= self.body.len() as i16 {
+                    return false;
+                }
+            }
+        }
+
+        return any_move;
+    }
+
+    // the first of `moves` that starts the longest walk this snake can make,
+    // if any of them doesn't kill it straight away
+    pub fn find_longest_survival(
+        self: &Self,
+        b: &Board,
+        moves: &[Direction],
+        budget: Duration,
+    ) -> Option<Survival> {
+        let mut search = LongestPath::new(b, self, budget);
+        let mut best: Option<Survival> = None;
+
+        for dir in moves {
+            let next = match self
+                .head
+                .get_next_coord(*dir, b)
+                .and_then(|c| search.grid.index(&c))
+            {
+                Some(i) => i,
+                None => continue,
+            };
+
+            if !search.is_open(next, 1) {
+                continue;
+            }
+
+            let health = match search.health_after(next, self.health) {
+                Some(h) => h,
+                None => continue,
+            };
+
+            let turns = 1 + search.search(next, 1, health);
+            if best.is_none_or(|s| turns > s.turns) {
+                best = Some(Survival {
+                    first: *dir,
+                    turns,
+                    exact: false,
+                });
+            }
+        }
+
+        return best.map(|s| Survival {
+            exact: !search.cut_short,
+            ..s
+        });
+    }
+
+    // when every option is a dead end, stop weighing space and pick the
+    // move that wastes the fewest cells, hoping the pocket opens or an
+    // opponent dies first
+    pub fn survive_when_trapped(self: &Self, sm: &mut ScoredMoves, b: &Board) {
+        if !self.is_trapped(sm, b) {
+            return;
+        }
+
+        if let Some(s) = self.find_longest_survival(b, &sm.get_alive_moves(), SURVIVAL_BUDGET) {
+            sm.get_mut(s.first).score += SURVIVAL_WEIGHT;
+            println!(
+                "[trapped]: going {} survives {} turns",
+                s.first.get_str(),
+                s.turns
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    fn longest(f: &Fixture, moves: &[Direction]) -> Option<(Direction, u16, bool)> {
+        let b = f.board();
+        return f
+            .you()
+            .find_longest_survival(&b, moves, SURVIVAL_BUDGET)
+            .map(|s| (s.first, s.turns, s.exact));
+    }
+
+    #[test]
+    fn a_dead_end_corridor_lasts_its_length() {
+        let f = Fixture::new(8, 1, vec![snake("me", &[(3, 0), (2, 0), (1, 0)], 90)]);
+
+        assert_eq!(
+            longest(&f, &Direction::ALL),
+            Some((Direction::Right, 4, true))
+        );
+        // back into our own neck isn't a move at all
+        assert_eq!(longest(&f, &[Direction::Left]), None);
+    }
+
+    #[test]
+    fn the_longer_side_is_chosen() {
+        // freshly spawned, three cells to the left and four to the right
+        let f = Fixture::new(8, 1, vec![snake("me", &[(3, 0), (3, 0), (3, 0)], 90)]);
+
+        assert_eq!(
+            longest(&f, &Direction::ALL),
+            Some((Direction::Right, 4, true))
+        );
+        assert_eq!(
+            longest(&f, &[Direction::Left]),
+            Some((Direction::Left, 3, true))
+        );
+    }
+
+    #[test]
+    fn chasing_our_tail_lasts_until_we_starve() {
+        let f = Fixture::new(2, 2, vec![snake("me", &[(0, 0), (0, 1), (1, 1)], 5)]);
+
+        // the move that takes our last point of health kills us
+        assert_eq!(
+            longest(&f, &Direction::ALL),
+            Some((Direction::Right, 4, true))
+        );
+    }
+
+    #[test]
+    fn food_on_the_way_keeps_us_going() {
+        let starving = Fixture::new(3, 1, vec![snake("me", &[(0, 0), (0, 0)], 2)]);
+        let fed = Fixture::new(3, 1, vec![snake("me", &[(0, 0), (0, 0)], 2)]).with_food(&[(1, 0)]);
+
+        let (_, hungry, _) = longest(&starving, &Direction::ALL).unwrap();
+        let (_, eating, _) = longest(&fed, &Direction::ALL).unwrap();
+        assert_eq!(hungry, 1);
+        assert!(eating > hungry);
+    }
+
+    #[test]
+    fn open_space_is_not_a_trap() {
+        let f = Fixture::new(8, 1, vec![snake("me", &[(3, 0), (3, 0), (3, 0)], 90)]);
+        let sm = ScoredMoves::init();
+        assert!(!f.you().is_trapped(&sm, &f.board()));
+    }
+
+    #[test]
+    fn a_trapped_snake_stalls_as_long_as_it_can() {
+        // too long for either side of the corridor, the right lasts longer
+        let f = Fixture::new(8, 1, vec![snake("me", &[(3, 0); 6], 90)]);
+        let b = f.board();
+        let me = f.you();
+
+        let mut sm = ScoredMoves::init();
+        me.avoid_walls(&mut sm, &b);
+        assert!(me.is_trapped(&sm, &b));
+
+        me.survive_when_trapped(&mut sm, &b);
+        assert_eq!(sm.get(Direction::Right).score, SURVIVAL_WEIGHT);
+        assert_eq!(sm.get(Direction::Left).score, 0);
+    }
+
+    #[test]
+    fn a_move_ruled_out_stays_out_when_trapped() {
+        // only the right has room for our body, but it's been ruled out
+        // and flood fill has since scored it
+        let f = Fixture::new(8, 1, vec![snake("me", &[(3, 0); 5], 90)]);
+        let b = f.board();
+        let me = f.you();
+
+        let mut sm = ScoredMoves::init();
+        me.avoid_walls(&mut sm, &b);
+        sm.kill(Direction::Right);
+        me.use_flood_fill(&mut sm, &b);
+        assert!(sm.get(Direction::Right).score > ScoredMoves::DEATH);
+        assert!(me.is_trapped(&sm, &b));
+
+        let before = sm.clone();
+        me.survive_when_trapped(&mut sm, &b);
+        assert_eq!(
+            sm.get(Direction::Left).score,
+            before.get(Direction::Left).score + SURVIVAL_WEIGHT
+        );
+        assert_eq!(sm.get(Direction::Right), before.get(Direction::Right));
+    }
+}
@@ -0,0 +1,188 @@
+use super::distance::{DistanceMap, UNREACHABLE};
+use super::{Battlesnake, Board, Direction, ScoredMoves};
+
+const TERRITORY_WEIGHT: i16 = 1;
+
+// voronoi partition of the board: every cell goes to the snake that can get
+// there first. when several arrive together the strictly longest one takes
+// it, equal lengths would trade heads so nobody does
+pub struct Territory {
+    counts: Vec<u16>,
+}
+
+impl Territory {
+    // `delays` is added to each snake's distances, for snakes that are a move
+    // behind (or ahead of) the others on this board
+    pub fn new(b: &Board, dm: &DistanceMap, delays: &[u16]) -> Territory {
+        let snakes = dm.snake_count();
+        let mut counts = vec![0u16; snakes];
+
+        for cell in 0..dm.get_grid().cells() {
+            let mut best = UNREACHABLE;
+            let mut owner: Option<usize> = None;
+            let mut contested = false;
+
+            for s in 0..snakes {
+                let d = dm.get_at(s, cell);
+                if d == UNREACHABLE {
+                    continue;
+                }
+
+                let d = d.saturating_add(delays.get(s).copied().unwrap_or(0));
+
+                if d < best {
+                    best = d;
+                    owner = Some(s);
+                    contested = false;
+                } else if d == best {
+                    let current = owner.map(|o| b.snakes[o].length).unwrap_or(0);
+                    let challenger = b.snakes[s].length;
+
+                    if challenger > current {
+                        owner = Some(s);
+                        contested = false;
+                    } else if challenger == current {
+                        contested = true;
+                    }
+                }
+            }
+
+            if let Some(o) = owner
+                && !contested
+            {
+                counts[o] += 1;
+            }
+        }
+
+        return Territory { counts };
+    }
+
+    pub fn get_count(self: &Self, snake: usize) -> u16 {
+        return self.counts.get(snake).copied().unwrap_or(0);
+    }
+
+    // our cells minus the biggest opponent's
+    pub fn get_margin(self: &Self, snake: usize) -> i16 {
+        let ours = self.get_count(snake) as i16;
+        let best_opponent = (0..self.counts.len())
+            .filter(|s| *s != snake)
+            .map(|s| self.get_count(s) as i16)
+            .max()
+            .unwrap_or(0);
+
+        return ours - best_opponent;
+    }
+}
+
+impl Battlesnake {
+    // unlike use_flood_fill this only credits the cells we'd reach before
+    // anyone else, so an open area an opponent is about to close off is
+    // worth much less than its size
+    pub fn use_territory(self: &Self, sm: &mut ScoredMoves, b: &Board) {
+        for dir in Direction::ALL {
+            if sm.is_dead(dir) {
+                continue;
+            }
+
+            let next = match self.head.get_next_coord(dir, b) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            let moved = b.with_snake_moved(&self.id, next);
+            let dm = DistanceMap::new(&moved);
+
+            let me = match dm.index_of(&self.id) {
+                Some(i) => i,
+                None => continue,
+            };
+
+            // we've spent a move the others haven't made yet
+            let mut delays = vec![0u16; dm.snake_count()];
+            delays[me] = 1;
+
+            let territory = Territory::new(&moved, &dm, &delays);
+            let margin = territory.get_margin(me);
+
+            sm.get_mut(dir).score += margin * TERRITORY_WEIGHT;
+            println!(
+                "[territory {}]: {} (margin {})",
+                dir.get_str(),
+                territory.get_count(me),
+                margin
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{Fixture, snake};
+
+    #[test]
+    fn ties_go_to_the_longer_snake_or_nobody() {
+        let even = Fixture::new(
+            5,
+            1,
+            vec![snake("me", &[(0, 0)], 90), snake("b", &[(4, 0)], 90)],
+        )
+        .board();
+        let dm = DistanceMap::new(&even);
+        let t = Territory::new(&even, &dm, &[0, 0]);
+        // the middle cell would be a head to head
+        assert_eq!((t.get_count(0), t.get_count(1)), (2, 2));
+        assert_eq!(t.get_margin(0), 0);
+
+        let longer = Fixture::new(
+            5,
+            1,
+            vec![
+                snake("me", &[(0, 0)], 90),
+                snake("b", &[(4, 0), (4, 0)], 90),
+            ],
+        )
+        .board();
+        let dm = DistanceMap::new(&longer);
+        let t = Territory::new(&longer, &dm, &[0, 0]);
+        assert_eq!((t.get_count(0), t.get_count(1)), (2, 3));
+        assert_eq!(t.get_margin(0), -1);
+    }
+
+    #[test]
+    fn a_delay_hands_cells_over() {
+        let b = Fixture::new(
+            6,
+            1,
+            vec![snake("me", &[(0, 0)], 90), snake("b", &[(5, 0)], 90)],
+        )
+        .board();
+        let dm = DistanceMap::new(&b);
+
+        let t = Territory::new(&b, &dm, &[0, 0]);
+        assert_eq!((t.get_count(0), t.get_count(1)), (3, 3));
+
+        let t = Territory::new(&b, &dm, &[1, 0]);
+        assert_eq!((t.get_count(0), t.get_count(1)), (2, 3));
+    }
+
+    #[test]
+    fn moving_toward_the_middle_claims_more() {
+        let f = Fixture::new(
+            7,
+            7,
+            vec![
+                snake("me", &[(1, 3), (0, 3)], 90),
+                snake("b", &[(5, 3), (6, 3)], 90),
+            ],
+        );
+        let b = f.board();
+
+        let mut sm = ScoredMoves::init();
+        f.you().avoid_own_neck(&mut sm, &b);
+        f.you().use_territory(&mut sm, &b);
+
+        assert!(sm.get(Direction::Right).score > sm.get(Direction::Up).score);
+        assert_eq!(sm.get(Direction::Up).score, sm.get(Direction::Down).score);
+    }
+}
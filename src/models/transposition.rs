@@ -0,0 +1,241 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::Direction;
+
+// whether a stored score is exact or only a limit, when an alpha-beta
+// search cut the position off before seeing every move
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+// a position's score as found by a search `depth` moves deep, with the move
+// that got it. depth 0 is a static evaluation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Entry {
+    depth: u8,
+    score: i16,
+    bound: Bound,
+    best: Option<Direction>,
+}
+
+// set on every stored entry so an empty slot never reads as one
+const OCCUPIED: u64 = 1 << 31;
+
+impl Entry {
+    pub fn get_depth(self: &Self) -> u8 {
+        return self.depth;
+    }
+
+    pub fn get_score(self: &Self) -> i16 {
+        return self.score;
+    }
+
+    pub fn get_bound(self: &Self) -> Bound {
+        return self.bound;
+    }
+
+    pub fn get_best(self: &Self) -> Option<Direction> {
+        return self.best;
+    }
+
+    // score in the low 16 bits, then depth, bound and best move
+    fn pack(self: &Self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let best = match self.best {
+            None => 0,
+            Some(d) => 1 + Direction::ALL.iter().position(|a| *a == d).unwrap_or(0) as u64,
+        };
+
+        return self.score as u16 as u64
+            | (self.depth as u64) << 16
+            | bound << 24
+            | best << 26
+            | OCCUPIED;
+    }
+
+    fn unpack(data: u64) -> Option<Entry> {
+        if data & OCCUPIED == 0 {
+            return None;
+        }
+
+        let bound = match (data >> 24) & 0b11 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let best = match (data >> 26) & 0b111 {
+            0 => None,
+            d => Direction::ALL.get(d as usize - 1).copied(),
+        };
+
+        return Some(Entry {
+            depth: (data >> 16) as u8,
+            score: data as u16 as i16,
+            bound,
+            best,
+        });
+    }
+}
+
+// fixed size table indexed by the low bits of a zobrist hash, shared by
+// every thread searching the same game without a lock. each slot holds the
+// entry and the key xored with it, so a slot torn by two threads writing at
+// once fails the key check and reads as empty. on a clash the deeper result
+// is kept, since it cost more to find
+pub struct TranspositionTable {
+    slots: Vec<(AtomicU64, AtomicU64)>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+    pub fn new(bits: u32) -> TranspositionTable {
+        let size = 1usize << bits;
+        return TranspositionTable {
+            slots: (0..size)
+                .map(|_| (AtomicU64::new(0), AtomicU64::new(0)))
+                .collect(),
+            mask: size - 1,
+        };
+    }
+
+    fn slot(self: &Self, key: u64) -> &(AtomicU64, AtomicU64) {
+        return &self.slots[key as usize & self.mask];
+    }
+
+    // whatever is stored for this position, however deep
+    pub fn get(self: &Self, key: u64) -> Option<Entry> {
+        let (check, data) = self.slot(key);
+        let data = data.load(Ordering::Relaxed);
+
+        if check.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+
+        return Entry::unpack(data);
+    }
+
+    // the exact score for this position if it was searched at least `depth` deep
+    pub fn probe(self: &Self, key: u64, depth: u8) -> Option<i16> {
+        return match self.get(key) {
+            Some(e) if e.depth >= depth && e.bound == Bound::Exact => Some(e.score),
+            _ => None,
+        };
+    }
+
+    pub fn store(
+        self: &Self,
+        key: u64,
+        depth: u8,
+        score: i16,
+        bound: Bound,
+        best: Option<Direction>,
+    ) {
+        let (check, data) = self.slot(key);
+        let old = data.load(Ordering::Relaxed);
+
+        if let Some(e) = Entry::unpack(old)
+            && check.load(Ordering::Relaxed) ^ old != key
+            && e.depth > depth
+        {
+            return;
+        }
+
+        let entry = Entry {
+            depth,
+            score,
+            bound,
+            best,
+        }
+        .pack();
+
+        data.store(entry, Ordering::Relaxed);
+        check.store(key ^ entry, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip() {
+        let table = TranspositionTable::new(4);
+        let cases = [
+            (0x1234_5678_9abc_def0, 0, 0, Bound::Exact, None),
+            (
+                0x0fed_cba9_8765_4321,
+                40,
+                -9_998,
+                Bound::Lower,
+                Some(Direction::Up),
+            ),
+            (
+                0x0000_0000_0000_0003,
+                255,
+                i16::MIN,
+                Bound::Upper,
+                Some(Direction::Left),
+            ),
+            (
+                0xffff_ffff_ffff_fff4,
+                7,
+                i16::MAX,
+                Bound::Exact,
+                Some(Direction::Down),
+            ),
+        ];
+
+        for (key, depth, score, bound, best) in cases {
+            table.store(key, depth, score, bound, best);
+            let e = table.get(key).unwrap();
+            assert_eq!(
+                (e.depth, e.score, e.bound, e.best),
+                (depth, score, bound, best)
+            );
+        }
+    }
+
+    #[test]
+    fn empty_and_other_keys_miss() {
+        let table = TranspositionTable::new(4);
+        assert!(table.get(0).is_none());
+        assert!(table.get(5).is_none());
+
+        table.store(5, 3, 10, Bound::Exact, None);
+        assert!(table.get(5 + 16).is_none());
+    }
+
+    #[test]
+    fn probe_needs_an_exact_deep_enough_score() {
+        let table = TranspositionTable::new(4);
+        table.store(1, 3, 42, Bound::Exact, None);
+        table.store(2, 3, 42, Bound::Lower, None);
+
+        assert_eq!(table.probe(1, 3), Some(42));
+        assert_eq!(table.probe(1, 4), None);
+        assert_eq!(table.probe(2, 1), None);
+    }
+
+    #[test]
+    fn deeper_entries_survive_a_clash() {
+        let table = TranspositionTable::new(4);
+        table.store(1, 5, 1, Bound::Exact, None);
+        table.store(1 + 16, 2, 2, Bound::Exact, None);
+        assert_eq!(table.probe(1, 5), Some(1));
+
+        // the same position is always replaced
+        table.store(1, 2, 3, Bound::Exact, None);
+        assert_eq!(table.probe(1, 2), Some(3));
+
+        table.store(1 + 16, 6, 4, Bound::Exact, None);
+        assert_eq!(table.probe(1 + 16, 6), Some(4));
+        assert!(table.get(1).is_none());
+    }
+}
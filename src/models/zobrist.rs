@@ -0,0 +1,176 @@
+use super::Board;
+use super::grid::Grid;
+
+// healths this close together hash the same, they rarely change the result.
+// below EXACT_HEALTH a snake could starve, or fall under the searches' low
+// health line, within a search's horizon, so every point counts
+pub const HEALTH_BUCKET: u8 = 10;
+pub const EXACT_HEALTH: u8 = 64;
+
+const SEED: u64 = 0x5eed_b1ab_5a4e_0001;
+
+const BOARD: u64 = 1;
+const SEGMENT: u64 = 2;
+const HEALTH: u64 = 3;
+const FOOD: u64 = 4;
+const HAZARD: u64 = 5;
+const START: u64 = 6;
+const PERSPECTIVE: u64 = 7;
+
+// splitmix64. keys are computed from (kind, slot, value) rather than kept in
+// tables so any board size and snake count gets them, and the same feature
+// always gets the same key
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    return z ^ (z >> 31);
+}
+
+fn key(kind: u64, slot: usize, value: usize) -> u64 {
+    return mix(SEED ^ mix(kind << 56 ^ (slot as u64) << 32 ^ value as u64));
+}
+
+pub fn board_key(grid: &Grid) -> u64 {
+    let shape = (grid.get_height() as usize) << 1 | grid.is_wrapped() as usize;
+    return key(BOARD, grid.get_width() as usize, shape);
+}
+
+// one body segment, counted from the tail so the key says where the cell
+// sits in the body. two segments stacked on a cell never cancel out
+pub fn segment_key(slot: usize, from_tail: usize, cell: usize) -> u64 {
+    return key(SEGMENT, slot, from_tail << 16 | cell);
+}
+
+// every segment of a body given head first. the whole body has to be rekeyed
+// when the snake moves, since every segment's place from the tail changes
+pub fn body_key(slot: usize, body: impl DoubleEndedIterator<Item = usize>) -> u64 {
+    let mut h = 0;
+    for (i, cell) in body.rev().enumerate() {
+        h ^= segment_key(slot, i, cell);
+    }
+    return h;
+}
+
+pub fn health_key(slot: usize, health: u8) -> u64 {
+    if health <= EXACT_HEALTH {
+        return key(HEALTH, slot, health as usize);
+    }
+    return key(
+        HEALTH,
+        slot,
+        u8::MAX as usize + (health / HEALTH_BUCKET) as usize,
+    );
+}
+
+pub fn food_key(cell: usize) -> u64 {
+    return key(FOOD, 0, cell);
+}
+
+pub fn hazard_key(cell: usize) -> u64 {
+    return key(HAZARD, 0, cell);
+}
+
+// for results that depend on a cell as well as the position, like flood fills
+pub fn start_key(cell: usize) -> u64 {
+    return key(START, 0, cell);
+}
+
+// for search results scored from one snake's side of the position
+pub fn perspective_key(slot: usize) -> u64 {
+    return key(PERSPECTIVE, slot, 0);
+}
+
+impl Board {
+    // snakes are keyed by their slot in `snakes`, so hashes only compare
+    // between boards of the same game
+    pub fn zobrist_hash(self: &Self) -> u64 {
+        let grid = Grid::new(self);
+        let mut h = board_key(&grid);
+
+        for (slot, snake) in self.snakes.iter().enumerate() {
+            let body: Vec<usize> = snake.body.iter().filter_map(|c| grid.index(c)).collect();
+            h ^= body_key(slot, body.into_iter());
+            h ^= health_key(slot, snake.health.min(u8::MAX as u16) as u8);
+        }
+
+        for f in &self.food {
+            if let Some(i) = grid.index(f) {
+                h ^= food_key(i);
+            }
+        }
+
+        for z in &self.hazards {
+            if let Some(i) = grid.index(z) {
+                h ^= hazard_key(i);
+            }
+        }
+
+        return h;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::fixtures::{Fixture, snake};
+
+    fn hash(body: &[(i32, i32)]) -> u64 {
+        return Fixture::new(5, 5, vec![snake("me", body, 90)])
+            .board()
+            .zobrist_hash();
+    }
+
+    #[test]
+    fn segment_order_is_hashed() {
+        // the same nine cells with the same head and tail, walked in a
+        // different order
+        let a = [
+            (0, 0),
+            (1, 0),
+            (1, 1),
+            (0, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+            (2, 1),
+            (2, 0),
+        ];
+        let b = [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+            (2, 1),
+            (1, 1),
+            (1, 0),
+            (2, 0),
+        ];
+
+        assert_ne!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn low_health_is_hashed_exactly() {
+        let at = |health: u16| {
+            Fixture::new(5, 5, vec![snake("me", &[(1, 1), (1, 2)], health)])
+                .board()
+                .zobrist_hash()
+        };
+
+        assert_ne!(at(1), at(2));
+        assert_ne!(at(41), at(49));
+        assert_ne!(at(64), at(65));
+        assert_eq!(at(91), at(99));
+    }
+
+    #[test]
+    fn stacked_segments_do_not_cancel() {
+        assert_ne!(hash(&[(1, 1), (1, 2), (1, 2)]), hash(&[(1, 1), (1, 2)]));
+        assert_ne!(hash(&[(1, 1), (1, 1), (1, 1)]), hash(&[(1, 1)]));
+        assert_ne!(
+            hash(&[(1, 1), (1, 2), (1, 2), (1, 2)]),
+            hash(&[(1, 1), (1, 1), (1, 2), (1, 2)])
+        );
+    }
+}